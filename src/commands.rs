@@ -0,0 +1,231 @@
+use crate::datapack_loader::DatapackLoader;
+use crate::datapacks::{Datapack, FileMap};
+use crate::manifest::{Manifest, MANIFEST_NAME};
+use crate::utils::{discover_datapacks, MergeError};
+use clap::ArgMatches;
+use colored::*;
+use indicatif::HumanBytes;
+use std::collections::BTreeSet;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+/// Write a starter manifest containing every datapacks inside `directory`
+pub fn init(directory: &Path, matches: &ArgMatches) -> Result<(), MergeError> {
+	let pack_type = crate::get_pack_type(matches);
+	let discovery = discover_datapacks(directory, matches.is_present("recursive"), pack_type)?;
+	if matches.is_present("verbose") {
+		discovery.print_rejected();
+	}
+	let datapacks: Vec<String> = discovery
+		.datapacks
+		.into_iter()
+		.map(|loader| loader.name)
+		.collect();
+	let manifest_path = directory.join(MANIFEST_NAME);
+
+	Manifest::starter(&datapacks, crate::default_name(pack_type))
+		.save(&manifest_path, matches.is_present("force"))?;
+
+	println!(
+		"Created manifest: '{}'",
+		manifest_path.display().to_string().cyan()
+	);
+
+	Ok(())
+}
+
+/// Print file tree of a datapack
+pub fn inspect(matches: &ArgMatches) -> Result<(), MergeError> {
+	let (name, datapack) = load_datapack(datapack_argument(matches, "datapack"))?;
+	let files = datapack.flatten();
+	let size: u64 = files.values().map(|data| data.len() as u64).sum();
+
+	println!("{}", name.bold());
+	print_tree(&files);
+	println!("{} files, {}", files.len(), HumanBytes(size));
+
+	Ok(())
+}
+
+/// Print files that were added, removed or modified between two datapacks
+pub fn diff(matches: &ArgMatches) -> Result<(), MergeError> {
+	let (original_name, original) = load_datapack(datapack_argument(matches, "original"))?;
+	let (other_name, other) = load_datapack(datapack_argument(matches, "other"))?;
+	let original_files = original.flatten();
+	let other_files = other.flatten();
+
+	println!("{} {}", "---".red(), original_name);
+	println!("{} {}", "+++".green(), other_name);
+
+	let changes = diff_files(&original_files, &other_files);
+	for change in &changes {
+		println!("{}", change);
+	}
+
+	let count = |kind: fn(&Change) -> bool| changes.iter().filter(|change| kind(change)).count();
+	println!(
+		"{} added, {} removed, {} modified",
+		count(|change| matches!(change, Change::Added(_))),
+		count(|change| matches!(change, Change::Removed(_))),
+		count(|change| matches!(change, Change::Modified(_)))
+	);
+
+	Ok(())
+}
+
+/// Check a datapack without writing anything, fail if there are any problem
+pub fn validate(matches: &ArgMatches) -> Result<(), MergeError> {
+	let (name, datapack) = load_datapack(datapack_argument(matches, "datapack"))?;
+	let errors = datapack.validate();
+
+	if errors.is_empty() {
+		println!("'{}' is a valid datapack", name.cyan());
+		Ok(())
+	} else {
+		for error in &errors {
+			eprintln!("{} {}", "error:".red().bold(), error);
+		}
+		Err(MergeError::InvalidDatapack(name, errors.len()))
+	}
+}
+
+/// Unpack zipped datapack into a directory
+pub fn extract(matches: &ArgMatches) -> Result<(), MergeError> {
+	let path = datapack_argument(matches, "datapack");
+	if !path.is_file() {
+		return Err(MergeError::NotZip(path.to_path_buf()));
+	}
+
+	let output = match matches.value_of("output") {
+		Some(output) => PathBuf::from(output),
+		None => path.with_extension(""),
+	};
+	if output.exists() {
+		return Err(MergeError::AlreadyExists(output));
+	}
+
+	DatapackLoader::extract_to(path, &output)?;
+
+	println!(
+		"Extracted datapack to: '{}'",
+		output.display().to_string().cyan()
+	);
+
+	Ok(())
+}
+
+fn datapack_argument<'a>(matches: &'a ArgMatches, name: &str) -> &'a Path {
+	let value = matches.value_of(name).expect("Invalid datapack path");
+	Path::new(value)
+}
+
+/// Load datapack from either a directory or a zip file
+fn load_datapack(path: &Path) -> Result<(String, Datapack), MergeError> {
+	if !path.exists() {
+		return Err(MergeError::NotFound(path.to_path_buf()));
+	}
+
+	let loader = DatapackLoader::new(path)?;
+	let mut problems = Vec::new();
+	let result = Datapack::generate(&loader.path, &mut problems, |_| {});
+
+	for problem in problems {
+		eprintln!("{}", problem);
+	}
+
+	let (datapack, _) = result?;
+	Ok((loader.name, datapack))
+}
+
+/// Print every files as an indented tree
+pub fn print_tree(files: &FileMap) {
+	let mut previous: Vec<&OsStr> = Vec::new();
+	for (path, data) in files {
+		let components: Vec<&OsStr> = path.iter().collect();
+		let (file, directories) = components.split_last().expect("Empty file path");
+		let common = previous
+			.iter()
+			.zip(directories)
+			.take_while(|(a, b)| a == b)
+			.count();
+
+		for (depth, directory) in directories.iter().enumerate().skip(common) {
+			let directory = format!("{}/", directory.to_string_lossy());
+			println!("{}{}", indent(depth + 1), directory.blue().bold());
+		}
+
+		let size = format!("({})", HumanBytes(data.len() as u64));
+		println!(
+			"{}{} {}",
+			indent(directories.len() + 1),
+			file.to_string_lossy(),
+			size.dimmed()
+		);
+
+		previous = directories.to_vec();
+	}
+}
+
+fn indent(depth: usize) -> String {
+	"  ".repeat(depth)
+}
+
+/// Difference of a single file between two datapacks
+#[derive(Debug, PartialEq, Eq)]
+pub enum Change<'a> {
+	Added(&'a Path),
+	Removed(&'a Path),
+	Modified(&'a Path),
+}
+
+use std::fmt;
+impl fmt::Display for Change<'_> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Change::Added(path) => write!(f, "{}", format!("+ {}", path.display()).green()),
+			Change::Removed(path) => write!(f, "{}", format!("- {}", path.display()).red()),
+			Change::Modified(path) => write!(f, "{}", format!("~ {}", path.display()).yellow()),
+		}
+	}
+}
+
+fn diff_files<'a>(original: &'a FileMap, other: &'a FileMap) -> Vec<Change<'a>> {
+	let paths: BTreeSet<&PathBuf> = original.keys().chain(other.keys()).collect();
+	paths
+		.into_iter()
+		.filter_map(|path| match (original.get(path), other.get(path)) {
+			(Some(a), Some(b)) if a != b => Some(Change::Modified(path)),
+			(Some(_), Some(_)) => None,
+			(Some(_), None) => Some(Change::Removed(path)),
+			(None, Some(_)) => Some(Change::Added(path)),
+			(None, None) => None,
+		})
+		.collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn diff_kazuma_party() {
+		let mut original = FileMap::new();
+		original.insert(PathBuf::from("data/party/aqua"), b"goddess");
+		original.insert(PathBuf::from("data/party/darkness"), b"crusader");
+		original.insert(PathBuf::from("data/party/megumin"), b"archwizard");
+
+		let mut other = FileMap::new();
+		other.insert(PathBuf::from("data/party/aqua"), b"useless goddess");
+		other.insert(PathBuf::from("data/party/megumin"), b"archwizard");
+		other.insert(PathBuf::from("data/party/wiz"), b"lich");
+
+		assert_eq!(
+			diff_files(&original, &other),
+			vec![
+				Change::Modified(Path::new("data/party/aqua")),
+				Change::Removed(Path::new("data/party/darkness")),
+				Change::Added(Path::new("data/party/wiz")),
+			]
+		);
+	}
+}
@@ -2,78 +2,94 @@ use crate::utils::os_str_to_string;
 use std::fs;
 use std::fs::File;
 use std::fs::Metadata;
-use std::io::Result;
+use std::io::{ErrorKind, Result};
 use std::path::{Path, PathBuf};
-use tempfile::tempdir;
 use zip::read::ZipFile;
+use zip::result::ZipError;
 use zip::ZipArchive;
 
 /// Abstraction layer for datapack
 /// 
-/// Because datapack can come in either 'directory' or 'zip file' format
+/// Because datapack can come in either 'directory' or 'zip file' format.
+/// Both of them are read in place by `Datapack::generate()`
 #[derive(Clone, Debug)]
 pub struct DatapackLoader {
 	pub path: PathBuf,
 	pub name: String,
-	is_temp: bool,
 }
 
 impl DatapackLoader {
-	pub fn cleanup(&self) {
-		if self.is_temp {
-			fs::remove_dir_all(&self.path).unwrap();
-		}
+	pub fn new(origin: impl Into<PathBuf>) -> Result<DatapackLoader> {
+		let path = origin.into();
+		// `.` and `..` don't have a file name, the name of the folder they point to is used instead
+		let name = match path.canonicalize()?.file_name() {
+			Some(name) => os_str_to_string(name),
+			None => path.display().to_string(),
+		};
+		Ok(DatapackLoader { path, name })
 	}
 
-	pub fn new(origin: impl Into<PathBuf>) -> Result<DatapackLoader> {
-		let origin = origin.into();
-		let name = os_str_to_string(origin.file_name().unwrap());
-		if origin.is_file() {
-			let path = DatapackLoader::extract(origin)?;
-			Ok(DatapackLoader {
-				path,
-				name,
-				is_temp: true,
-			})
-		} else {
-			Ok(DatapackLoader {
-				path: origin,
-				name,
-				is_temp: false,
-			})
-		}
+	/// Datapack at `origin` named by its path relative to `root`, such as `world/datapacks/rem.zip`
+	pub fn relative(origin: PathBuf, root: &Path) -> DatapackLoader {
+		let relative = origin.strip_prefix(root).unwrap_or(&origin);
+		let name = relative
+			.iter()
+			.map(os_str_to_string)
+			.collect::<Vec<String>>()
+			.join("/");
+		DatapackLoader { path: origin, name }
 	}
 
-	/// Get metadata of a single file without having to loop through the entire datapack
-	pub fn peak(origin: &PathBuf, path: &str) -> Result<Metadata> {
-		if origin.is_file() {
-			let file = File::open(&origin)?;
-			let mut zip = ZipArchive::new(file)?;
+	/// Check that `origin` contains `pack.mcmeta` and the folder of `pack_type` without reading any of its files
+	///
+	/// Zip files are checked by the names in their central directory, nothing is extracted or decompressed.
+	/// Entries under that folder count even if the zip has no explicit directory records.
+	pub fn detect(origin: &Path, pack_type: PackType) -> std::result::Result<(), Rejection> {
+		let folder = pack_type.folder();
+		let (has_pack_meta, has_folder) = if origin.is_file() {
+			let file = File::open(origin).map_err(Rejection::Unreadable)?;
+			let zip = ZipArchive::new(file).map_err(Rejection::CorruptZip)?;
+			let has_pack_meta = zip.file_names().any(|name| name == "pack.mcmeta");
+			let has_folder = zip.file_names().any(|name| {
+				let inside = name.strip_prefix(folder);
+				inside.is_some_and(|rest| rest.starts_with('/'))
+			});
+			(has_pack_meta, has_folder)
+		} else {
+			let is = |path: &str, check: fn(&Metadata) -> bool| match origin.join(path).metadata() {
+				Ok(metadata) => Ok(check(&metadata)),
+				Err(error) if error.kind() == ErrorKind::NotFound => Ok(false),
+				Err(error) => Err(Rejection::Unreadable(error)),
+			};
+			(
+				is("pack.mcmeta", Metadata::is_file)?,
+				is(folder, Metadata::is_dir)?,
+			)
+		};
 
-			let temp_dir = tempdir()?;
-			let file = zip.by_name(path)?;
-			let path = DatapackLoader::materialize_reader(file, temp_dir.path())?;
-			fs::metadata(path)
+		if !has_pack_meta {
+			Err(Rejection::MissingPackMeta)
+		} else if !has_folder {
+			Err(Rejection::MissingFolder(folder))
 		} else {
-			origin.join(path).metadata()
+			Ok(())
 		}
 	}
 
-	fn extract(origin: PathBuf) -> Result<PathBuf> {
-		let directory = tempdir()?;
-
-		let file = File::open(&origin)?;
+	/// Unpack every files inside zipped datapack at `origin` into `output` directory
+	pub fn extract_to(origin: &Path, output: &Path) -> Result<()> {
+		let file = File::open(origin)?;
 		let mut zip = ZipArchive::new(file)?;
 		for n in 0..zip.len() {
 			let file = zip.by_index(n)?;
-			DatapackLoader::materialize_reader(file, directory.path())?;
+			DatapackLoader::materialize_reader(file, output)?;
 		}
 
-		Ok(directory.into_path())
+		Ok(())
 	}
 
-	fn materialize_reader(mut reader: ZipFile, output: &Path) -> Result<PathBuf> {
-		let location = output.join(reader.sanitized_name());
+	fn materialize_reader(mut reader: ZipFile, output: &Path) -> Result<()> {
+		let location = output.join(reader.mangled_name());
 		if reader.is_dir() {
 			fs::create_dir_all(&location)?;
 		} else {
@@ -81,10 +97,173 @@ impl DatapackLoader {
 				fs::create_dir_all(parent)?;
 			}
 
-			let mut writer = File::create(&location).unwrap();
+			let mut writer = File::create(&location)?;
 			std::io::copy(&mut reader, &mut writer)?;
 		}
 
-		Ok(location)
+		Ok(())
+	}
+}
+
+/// Kind of packs being merged, decide which folder a pack must have
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PackType {
+	#[default]
+	Data,
+	Resource,
+}
+
+impl PackType {
+	pub fn folder(self) -> &'static str {
+		match self {
+			PackType::Data => "data",
+			PackType::Resource => "assets",
+		}
+	}
+
+	/// Name of this kind of pack as it is shown to players
+	pub fn noun(self) -> &'static str {
+		match self {
+			PackType::Data => "datapack",
+			PackType::Resource => "resource pack",
+		}
+	}
+}
+
+/// Reason why an entry is not considered a datapack
+#[derive(Debug)]
+pub enum Rejection {
+	/// File that is neither a folder nor a zip file, Minecraft would not load it either
+	NotPack,
+	MissingPackMeta,
+	/// Missing `data` folder for datapacks or `assets` folder for resource packs
+	MissingFolder(&'static str),
+	CorruptZip(ZipError),
+	Unreadable(std::io::Error),
+	/// Zipped datapack that was also found unpacked at this path, the folder is used instead
+	Duplicate(PathBuf),
+}
+
+impl Rejection {
+	/// Short name of the reason, used in JSON report
+	pub fn name(&self) -> &'static str {
+		match self {
+			Rejection::NotPack => "not-folder-or-zip",
+			Rejection::MissingPackMeta => "missing-pack-meta",
+			Rejection::MissingFolder("assets") => "missing-assets-folder",
+			Rejection::MissingFolder(_) => "missing-data-folder",
+			Rejection::CorruptZip(_) => "corrupt-zip",
+			Rejection::Unreadable(_) => "unreadable",
+			Rejection::Duplicate(_) => "duplicate",
+		}
+	}
+}
+
+use std::fmt;
+impl fmt::Display for Rejection {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Rejection::NotPack => write!(f, "not a folder or zip"),
+			Rejection::MissingPackMeta => write!(f, "missing pack.mcmeta"),
+			Rejection::MissingFolder(folder) => write!(f, "missing {} folder", folder),
+			Rejection::CorruptZip(error) => write!(f, "corrupt zip: {}", error),
+			Rejection::Unreadable(error) => write!(f, "unreadable: {}", error),
+			Rejection::Duplicate(folder) => write!(f, "duplicate of '{}'", folder.display()),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::io::Write;
+	use zip::write::FileOptions;
+	use zip::ZipWriter;
+
+	fn zip_with(directory: &Path, files: &[&str]) -> PathBuf {
+		let path = directory.join("datapack.zip");
+		let mut zip = ZipWriter::new(File::create(&path).unwrap());
+		for name in files {
+			zip.start_file(*name, FileOptions::default()).unwrap();
+			zip.write_all(b"{}").unwrap();
+		}
+		zip.finish().unwrap();
+		path
+	}
+
+	#[test]
+	fn detect_zip_without_directory_records() {
+		let directory = tempfile::tempdir().unwrap();
+		let path = zip_with(
+			directory.path(),
+			&[
+				"pack.mcmeta",
+				"data/rem/functions/return_by_death.mcfunction",
+			],
+		);
+		assert!(DatapackLoader::detect(&path, PackType::Data).is_ok());
+	}
+
+	#[test]
+	fn detect_zip_from_central_directory_only() {
+		let directory = tempfile::tempdir().unwrap();
+		let path = zip_with(
+			directory.path(),
+			&["pack.mcmeta", "data/rem/functions/tea.mcfunction"],
+		);
+
+		// Damage local header of the first entry, only the central directory is still readable
+		let mut data = fs::read(&path).unwrap();
+		data[..4].copy_from_slice(b"RAM!");
+		fs::write(&path, data).unwrap();
+
+		assert!(ZipArchive::new(File::open(&path).unwrap())
+			.unwrap()
+			.by_index(0)
+			.is_err());
+		assert!(DatapackLoader::detect(&path, PackType::Data).is_ok());
+	}
+
+	#[test]
+	fn reject_zip_without_data_folder() {
+		let directory = tempfile::tempdir().unwrap();
+		let path = zip_with(directory.path(), &["pack.mcmeta", "assets/ram.json"]);
+		match DatapackLoader::detect(&path, PackType::Data) {
+			Err(Rejection::MissingFolder("data")) => (),
+			other => panic!("Unexpected result: {:?}", other),
+		}
+		assert!(DatapackLoader::detect(&path, PackType::Resource).is_ok());
+	}
+
+	#[test]
+	fn extract_into_unwritable_output() {
+		let directory = tempfile::tempdir().unwrap();
+		let path = zip_with(directory.path(), &["pack.mcmeta"]);
+		let output = directory.path().join("output");
+		fs::create_dir_all(output.join("pack.mcmeta")).unwrap();
+
+		assert!(DatapackLoader::extract_to(&path, &output).is_err());
+	}
+
+	#[test]
+	fn name_datapack_from_parent_directory() {
+		let directory = tempfile::tempdir().unwrap();
+		let path = directory.path().join("roswaal");
+		fs::create_dir_all(&path).unwrap();
+
+		let loader = DatapackLoader::new(path.join("..").join("roswaal").join(".")).unwrap();
+		assert_eq!(loader.name, "roswaal");
+		assert!(DatapackLoader::new(path.join("missing")).is_err());
+	}
+
+	#[test]
+	fn reject_corrupt_zip() {
+		let directory = tempfile::tempdir().unwrap();
+		let path = directory.path().join("beatrice.zip");
+		fs::write(&path, b"I suppose this is not a zip").unwrap();
+		match DatapackLoader::detect(&path, PackType::Data) {
+			Err(Rejection::CorruptZip(_)) => (),
+			other => panic!("Unexpected result: {:?}", other),
+		}
 	}
 }
@@ -1,9 +1,533 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 /// Representing JSON structure of "Tags" in datapack
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct Tag {
 	#[serde(skip_serializing_if = "Option::is_none")]
 	pub replace: Option<bool>,
-	pub values: Vec<String>,
+	pub values: Vec<TagValue>,
+}
+
+/// A single entry inside tag's `values`, either `"id"` or `{ "id": "id", "required": false }`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum TagValue {
+	Id(String),
+	Entry {
+		id: String,
+		#[serde(default = "required_by_default")]
+		required: bool,
+	},
+}
+
+fn required_by_default() -> bool {
+	true
+}
+
+impl TagValue {
+	pub fn id(&self) -> &str {
+		match self {
+			TagValue::Id(id) => id,
+			TagValue::Entry { id, .. } => id,
+		}
+	}
+
+	pub fn is_required(&self) -> bool {
+		match self {
+			TagValue::Id(_) => true,
+			TagValue::Entry { required, .. } => *required,
+		}
+	}
+
+	/// Entry that is required by any datapack stay required after merging
+	fn merge(&mut self, other: &TagValue) {
+		if let TagValue::Entry { required, .. } = self {
+			*required |= other.is_required();
+		}
+	}
+}
+
+impl From<&str> for TagValue {
+	fn from(id: &str) -> TagValue {
+		TagValue::Id(id.to_string())
+	}
+}
+
+impl Tag {
+	/// Combine values of both tags, `other`'s values are placed after this tag's values.
+	///
+	/// If `other` has `replace: true`, this tag's values will be discarded just like how Minecraft loads tags.
+	/// Duplicated ids only keep their first occurrence and stay required if any of them is required.
+	pub fn merge(self, other: Tag) -> Tag {
+		// Merged tag still need to replace vanilla's tag if any of the original tags did
+		let replace = if self.is_replace() || other.is_replace() {
+			Some(true)
+		} else {
+			self.replace.or(other.replace)
+		};
+
+		let values = if other.is_replace() {
+			other.values
+		} else {
+			let mut values = self.values;
+			values.extend(other.values);
+			values
+		};
+
+		let mut result: Vec<TagValue> = Vec::with_capacity(values.len());
+		for value in values {
+			match result.iter_mut().find(|other| other.id() == value.id()) {
+				Some(original) => original.merge(&value),
+				None => result.push(value),
+			}
+		}
+
+		Tag {
+			replace,
+			values: result,
+		}
+	}
+
+	pub fn is_replace(&self) -> bool {
+		self.replace == Some(true)
+	}
+}
+
+/// Representing JSON structure of "pack.mcmeta" in datapack
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PackMeta {
+	pub pack: Pack,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub filter: Option<Filter>,
+	/// Other sections such as `features` or `overlays`, kept from the higher priority datapack
+	#[serde(flatten)]
+	pub other: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Pack {
+	pub pack_format: u32,
+	pub description: serde_json::Value,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub supported_formats: Option<FormatRange>,
+	#[serde(flatten)]
+	pub other: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Range of pack formats a datapack supports, either `16`, `[16, 18]` or `{ "min_inclusive": 16, "max_inclusive": 18 }`
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum FormatRange {
+	Single(u32),
+	List([u32; 2]),
+	Object {
+		min_inclusive: u32,
+		max_inclusive: u32,
+	},
+}
+
+impl FormatRange {
+	pub fn bounds(self) -> (u32, u32) {
+		match self {
+			FormatRange::Single(format) => (format, format),
+			FormatRange::List([min, max]) => (min, max),
+			FormatRange::Object {
+				min_inclusive,
+				max_inclusive,
+			} => (min_inclusive, max_inclusive),
+		}
+	}
+
+	pub fn contains(self, format: u32) -> bool {
+		let (min, max) = self.bounds();
+		min <= format && format <= max
+	}
+
+	/// Smallest range that cover both ranges
+	fn union(self, other: FormatRange) -> FormatRange {
+		let (min, max) = self.bounds();
+		let (other_min, other_max) = other.bounds();
+		FormatRange::from_bounds(min.min(other_min), max.max(other_max))
+	}
+
+	fn from_bounds(min: u32, max: u32) -> FormatRange {
+		if min == max {
+			FormatRange::Single(min)
+		} else {
+			FormatRange::List([min, max])
+		}
+	}
+}
+
+/// Files that Minecraft should ignore from lower priority datapacks
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct Filter {
+	pub block: Vec<FilterPattern>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct FilterPattern {
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub namespace: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub path: Option<String>,
+}
+
+impl PackMeta {
+	/// Supported pack formats of this datapack, only `pack_format` if `supported_formats` is missing
+	pub fn supported_formats(&self) -> FormatRange {
+		self.pack
+			.supported_formats
+			.unwrap_or(FormatRange::Single(self.pack.pack_format))
+	}
+
+	/// Combine `pack.mcmeta` of two datapacks, `other` has higher priority.
+	///
+	/// The highest `pack_format` is used and `supported_formats` cover every formats supported by both datapacks.
+	/// Returns the incompatible format range if any datapack explicitly declares `supported_formats`
+	/// that does not include the merged `pack_format`.
+	/// `description` is left to the caller since it depends on every merged datapacks.
+	pub fn merge(self, other: PackMeta) -> Result<PackMeta, FormatRange> {
+		let pack_format = self.pack.pack_format.max(other.pack.pack_format);
+		for meta in &[&self, &other] {
+			if let Some(range) = meta.pack.supported_formats {
+				if !range.contains(pack_format) {
+					return Err(range);
+				}
+			}
+		}
+
+		let supported_formats = match (self.pack.supported_formats, other.pack.supported_formats) {
+			(None, None) => None,
+			_ => Some(self.supported_formats().union(other.supported_formats())),
+		};
+
+		let filter = match (self.filter, other.filter) {
+			(Some(mut filter), Some(other)) => {
+				for pattern in other.block {
+					if !filter.block.contains(&pattern) {
+						filter.block.push(pattern);
+					}
+				}
+				Some(filter)
+			}
+			(filter, other) => filter.or(other),
+		};
+
+		let mut pack_other = self.pack.other;
+		pack_other.extend(other.pack.other);
+		let mut meta_other = self.other;
+		meta_other.extend(other.other);
+
+		Ok(PackMeta {
+			pack: Pack {
+				pack_format,
+				description: other.pack.description,
+				supported_formats,
+				other: pack_other,
+			},
+			filter,
+			other: meta_other,
+		})
+	}
+}
+
+/// Representing JSON structure of "Loot Tables" in datapack, pools are kept as raw JSON
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LootTable {
+	#[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+	pub kind: Option<String>,
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub pools: Vec<serde_json::Value>,
+	/// Other fields such as `functions` or `random_sequence`, kept from the higher priority loot table
+	#[serde(flatten)]
+	pub other: serde_json::Map<String, serde_json::Value>,
+}
+
+impl LootTable {
+	/// Append pools of this loot table after pools of `other` higher priority loot table.
+	///
+	/// Pools identical to one of `other`'s pools are only kept once. Pool that has the same entries as one of `other`'s pools
+	/// but different rolls, conditions or functions cannot be reconciled so only `other`'s pool is kept.
+	/// Returns the merged loot table along with description of every problems found.
+	pub fn merge(self, other: LootTable) -> (LootTable, Vec<String>) {
+		let mut notes = Vec::new();
+		let mut pools = other.pools;
+		let winners = pools.len();
+
+		for (index, pool) in self.pools.into_iter().enumerate() {
+			// Only compare with `other`'s own pools, a pool listed twice on purpose rolls twice
+			if pools[..winners].contains(&pool) {
+				continue;
+			}
+
+			let modified = pools[..winners].iter().position(|other| {
+				other.get("entries").is_some() && other.get("entries") == pool.get("entries")
+			});
+			match modified {
+				Some(position) => notes.push(format!(
+					"pool #{} has the same entries as higher priority pool #{} but different settings, only the higher priority one was kept",
+					index + 1,
+					position + 1
+				)),
+				None => pools.push(pool),
+			}
+		}
+
+		let loot_table = LootTable {
+			kind: other.kind.or(self.kind),
+			pools,
+			other: merge_fields(self.other, other.other, &mut notes),
+		};
+		(loot_table, notes)
+	}
+}
+
+/// Representing JSON structure of "Advancements" in datapack
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Advancement {
+	#[serde(default)]
+	pub criteria: serde_json::Map<String, serde_json::Value>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub requirements: Option<Vec<Vec<String>>>,
+	/// Other fields such as `display`, `rewards` or `parent`, kept from the higher priority advancement
+	#[serde(flatten)]
+	pub other: serde_json::Map<String, serde_json::Value>,
+}
+
+impl Advancement {
+	/// Combine criteria and requirements of this advancement and `other` higher priority advancement.
+	///
+	/// Merged advancement requires every requirements of both advancements.
+	/// Returns the merged advancement along with description of every disagreements found.
+	pub fn merge(self, other: Advancement) -> (Advancement, Vec<String>) {
+		let mut notes = Vec::new();
+
+		let requirements = match (&self.requirements, &other.requirements) {
+			(None, None) => None,
+			_ => {
+				let mut requirements = other.requirements();
+				for group in self.requirements() {
+					if !requirements.iter().any(|other| same_group(other, &group)) {
+						requirements.push(group);
+					}
+				}
+				Some(requirements)
+			}
+		};
+
+		let mut criteria = other.criteria;
+		for (name, criterion) in self.criteria {
+			match criteria.get(&name) {
+				Some(other) if other != &criterion => notes.push(format!(
+					"criterion '{}' is different, only the higher priority one was kept",
+					name
+				)),
+				Some(_) => (),
+				None => {
+					criteria.insert(name, criterion);
+				}
+			}
+		}
+
+		let advancement = Advancement {
+			criteria,
+			requirements,
+			other: merge_fields(self.other, other.other, &mut notes),
+		};
+		(advancement, notes)
+	}
+
+	/// Requirements of this advancement, every criteria are required if `requirements` is missing
+	fn requirements(&self) -> Vec<Vec<String>> {
+		match &self.requirements {
+			Some(requirements) => requirements.clone(),
+			None => self
+				.criteria
+				.keys()
+				.map(|name| vec![name.clone()])
+				.collect(),
+		}
+	}
+}
+
+/// Representing JSON structure of language files in resource pack, translations are sorted by their key
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(transparent)]
+pub struct Lang {
+	pub translations: BTreeMap<String, serde_json::Value>,
+}
+
+impl Lang {
+	/// Add translations of this language file that `other` higher priority language file doesn't have.
+	///
+	/// Returns the merged language file along with every keys that both files translate differently.
+	pub fn merge(self, other: Lang) -> (Lang, Vec<String>) {
+		let mut notes = Vec::new();
+		let mut translations = other.translations;
+		for (key, value) in self.translations {
+			match translations.get(&key) {
+				Some(other) if other != &value => notes.push(format!(
+					"translation '{}' is different, only the higher priority one was kept",
+					key
+				)),
+				Some(_) => (),
+				None => {
+					translations.insert(key, value);
+				}
+			}
+		}
+
+		(Lang { translations }, notes)
+	}
+}
+
+/// Representing JSON structure of `sounds.json` in resource pack, sound events are keyed by their name
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(transparent)]
+pub struct Sounds {
+	pub events: BTreeMap<String, SoundEvent>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SoundEvent {
+	#[serde(default, skip_serializing_if = "is_false")]
+	pub replace: bool,
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub sounds: Vec<serde_json::Value>,
+	/// Other fields such as `subtitle`, kept from the higher priority sound event
+	#[serde(flatten)]
+	pub other: serde_json::Map<String, serde_json::Value>,
+}
+
+fn is_false(value: &bool) -> bool {
+	!value
+}
+
+impl Sounds {
+	/// Add sound events of this file to `other` higher priority file.
+	///
+	/// Sound lists of the same event are concatenated from lowest to highest priority,
+	/// unless the higher priority event has `replace: true`.
+	/// `replace: true` of the lower priority event is kept on the merged event.
+	/// Returns the merged file along with description of every disagreements found.
+	pub fn merge(self, other: Sounds) -> (Sounds, Vec<String>) {
+		let mut notes = Vec::new();
+		let mut events = other.events;
+
+		for (name, event) in self.events {
+			let higher = match events.remove(&name) {
+				Some(higher) if higher.replace => higher,
+				Some(higher) => {
+					let mut sounds = event.sounds;
+					for sound in higher.sounds {
+						if !sounds.contains(&sound) {
+							sounds.push(sound);
+						}
+					}
+
+					let mut event_notes = Vec::new();
+					let other = merge_fields(event.other, higher.other, &mut event_notes);
+					notes.extend(
+						event_notes
+							.into_iter()
+							.map(|note| format!("sound event '{}': {}", name, note)),
+					);
+					// Merged event still need to replace vanilla's sounds if the lower priority event did
+					SoundEvent {
+						replace: event.replace,
+						sounds,
+						other,
+					}
+				}
+				None => event,
+			};
+			events.insert(name, higher);
+		}
+
+		(Sounds { events }, notes)
+	}
+}
+
+/// Representing JSON structure of texture atlases in resource pack, sources are kept as raw JSON
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Atlas {
+	#[serde(default)]
+	pub sources: Vec<serde_json::Value>,
+	#[serde(flatten)]
+	pub other: serde_json::Map<String, serde_json::Value>,
+}
+
+impl Atlas {
+	/// Add sources of `other` higher priority atlas after sources of this atlas, identical sources are only kept once
+	pub fn merge(self, other: Atlas) -> (Atlas, Vec<String>) {
+		let mut notes = Vec::new();
+		let mut sources = self.sources;
+		for source in other.sources {
+			if !sources.contains(&source) {
+				sources.push(source);
+			}
+		}
+
+		let atlas = Atlas {
+			sources,
+			other: merge_fields(self.other, other.other, &mut notes),
+		};
+		(atlas, notes)
+	}
+}
+
+/// Requirement groups are the same if they contain the same criteria in any order
+fn same_group(group: &[String], other: &[String]) -> bool {
+	group.len() == other.len() && group.iter().all(|name| other.contains(name))
+}
+
+/// Add fields of `lower` that `higher` doesn't have, fields that both have but with different values are noted
+fn merge_fields(
+	lower: serde_json::Map<String, serde_json::Value>,
+	mut higher: serde_json::Map<String, serde_json::Value>,
+	notes: &mut Vec<String>,
+) -> serde_json::Map<String, serde_json::Value> {
+	for (key, value) in lower {
+		match higher.get(&key) {
+			Some(other) if other != &value => notes.push(format!(
+				"'{}' is different, only the higher priority one was kept",
+				key
+			)),
+			Some(_) => (),
+			None => {
+				higher.insert(key, value);
+			}
+		}
+	}
+	higher
+}
+
+/// Deeply combine two JSON values, `higher` wins when they cannot be combined
+///
+/// Objects are merged key by key and arrays keep `lower`'s items followed by `higher`'s items that are not already present.
+pub fn union_json(lower: serde_json::Value, higher: serde_json::Value) -> serde_json::Value {
+	use serde_json::Value;
+	match (lower, higher) {
+		(Value::Object(mut lower), Value::Object(higher)) => {
+			for (key, value) in higher {
+				let value = match lower.remove(&key) {
+					Some(original) => union_json(original, value),
+					None => value,
+				};
+				lower.insert(key, value);
+			}
+			Value::Object(lower)
+		}
+		(Value::Array(mut lower), Value::Array(higher)) => {
+			for value in higher {
+				if !lower.contains(&value) {
+					lower.push(value);
+				}
+			}
+			Value::Array(lower)
+		}
+		(_, higher) => higher,
+	}
 }
@@ -1,13 +1,14 @@
 use super::{
-	CompiledResult, DataTree, GeneratedResult, MergedResult, Namespace, Script, ScriptKind,
-	TreeError,
+	CompiledResult, DataTree, FileMap, GeneratedResult, MergeContext, MergeRule, MergeRules,
+	MergedResult, Namespace, OutputSink, PackMeta, Script, ScriptKind, Tag, TagValue, TreeError,
 };
 use crate::utils::os_str_to_string;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsStr;
 use std::fs::File;
-use std::path::PathBuf;
-use zip::write::FileOptions;
-use zip::ZipWriter;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use zip::ZipArchive;
 
 /// A struct representing a datapack as a whole
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -15,6 +16,8 @@ pub struct Datapack {
 	location: PathBuf,
 	pub name: String,
 	child: HashSet<Namespace>,
+	/// Namespaces inside `assets` folder, only resource packs have them
+	assets: HashSet<Namespace>,
 	files: HashSet<Script>,
 }
 
@@ -31,113 +34,504 @@ impl Datapack {
 			location,
 			name,
 			child,
+			assets: HashSet::default(),
 			files,
 		}
 	}
 
+	/// Datapack without any files that other datapacks are merged into, it doesn't exist on disk
+	pub fn empty(name: impl Into<String>) -> Datapack {
+		Datapack::new(PathBuf::new(), name, HashSet::default(), HashSet::default())
+	}
+
 	/// Because `Datapack` doesn't have the same data structure as the one implementing `DataTree`.
 	/// It cannot implement that trait itself so this function mimick `DataTree`'s generate() function
+	///
+	/// `path` can be either a directory or a zip file, zip file is read directly without being extracted
 	pub fn generate(
 		path: impl Into<PathBuf>,
+		problems: &mut Vec<TreeError>,
 		event: impl Fn(u64) + Copy,
 	) -> GeneratedResult<Datapack> {
 		let path = path.into();
 		if path.is_dir() {
 			let mut child = HashSet::default();
+			let mut assets = HashSet::default();
 			let mut files = HashSet::default();
 			let mut size = 0;
 			for entry in path.read_dir()? {
 				let entry = entry?;
 				let name = os_str_to_string(entry.file_name());
 
-				if name != "data" {
-					match Script::generate(entry, ScriptKind::Generic, event) {
+				if name != DATA_FOLDER && name != ASSETS_FOLDER {
+					let kind = Datapack::get_script_kind(&name);
+					match Script::generate(entry, kind, problems, event) {
 						Ok((script, child_size)) => {
 							files.insert(script);
 							size += child_size;
 						}
-						Err(error) => eprintln!("{}", error),
+						Err(error) => problems.push(error),
 					}
 				}
 			}
 
-			for entry in path.join("data").read_dir()? {
-				let entry = entry?;
+			for (folder, namespaces) in
+				&mut [(DATA_FOLDER, &mut child), (ASSETS_FOLDER, &mut assets)]
+			{
+				let folder = path.join(folder);
+				if !folder.is_dir() {
+					continue;
+				}
+
+				for entry in folder.read_dir()? {
+					let entry = entry?;
 
-				match Namespace::generate(entry, ScriptKind::default(), event) {
-					Ok((namespace, child_size)) => {
-						child.insert(namespace);
-						size += child_size;
+					match Namespace::generate(entry, ScriptKind::default(), problems, event) {
+						Ok((namespace, child_size)) => {
+							namespaces.insert(namespace);
+							size += child_size;
+						}
+						Err(error) => match error {
+							TreeError::FileInNamespace(_) => (),
+							_ => problems.push(error),
+						},
 					}
-					Err(error) => match error {
-						TreeError::FileInNamespace(_) => (),
-						_ => eprintln!("{}", error),
-					},
 				}
 			}
 
 			let name = os_str_to_string(&path.as_os_str());
 			let location = path;
-			let datapack = Datapack::new(location, name, child, files);
+			let mut datapack = Datapack::new(location, name, child, files);
+			datapack.assets = assets;
 			Ok((datapack, size))
+		} else if path.is_file() {
+			Datapack::generate_from_zip(path, problems, event)
 		} else {
 			Err(TreeError::FileInDatapack(path))
 		}
 	}
 
+	/// Build data tree straight from entries of zipped datapack
+	fn generate_from_zip(
+		path: PathBuf,
+		problems: &mut Vec<TreeError>,
+		event: impl Fn(u64) + Copy,
+	) -> GeneratedResult<Datapack> {
+		let mut zip = ZipArchive::new(File::open(&path)?)?;
+		let name = os_str_to_string(path.as_os_str());
+		let mut datapack = Datapack::new(&path, name, HashSet::default(), HashSet::default());
+		let mut size = 0;
+
+		for index in 0..zip.len() {
+			let mut file = match zip.by_index(index) {
+				Ok(file) => file,
+				Err(error) => {
+					problems.push(error.into());
+					continue;
+				}
+			};
+			let entry = file.mangled_name();
+			// Files directly inside `data` or `assets` folder are not namespaces so they are ignored
+			let parent = entry.parent();
+			if file.is_dir()
+				|| parent == Some(Path::new(DATA_FOLDER))
+				|| parent == Some(Path::new(ASSETS_FOLDER))
+			{
+				continue;
+			}
+
+			// Size declared by the zip is not trusted, `read_to_end` grows the buffer as needed
+			let mut data = Vec::new();
+			if let Err(error) = file.read_to_end(&mut data) {
+				problems.push(error.into());
+				continue;
+			}
+
+			size += data.len() as u64;
+			event(data.len() as u64);
+			datapack.insert_file(&entry, data);
+		}
+
+		Ok((datapack, size))
+	}
+
+	/// Rename this datapack to `name` and mark every files inside it as coming from this datapack
+	pub fn with_origin(mut self, name: impl Into<String>) -> Datapack {
+		self.name = name.into();
+		let name = &self.name;
+
+		for namespaces in &mut [&mut self.child, &mut self.assets] {
+			**namespaces = namespaces
+				.drain()
+				.map(|mut namespace| {
+					namespace.set_origin(name);
+					namespace
+				})
+				.collect();
+		}
+		self.files = self
+			.files
+			.drain()
+			.map(|mut script| {
+				script.set_origin(name);
+				script
+			})
+			.collect();
+
+		self
+	}
+
 	/// Because `Datapack` doesn't have the same data structure as the one implementing `DataTree`.
 	/// It cannot implement that trait itself so this function mimick `DataTree`'s merge() function
-	pub fn merge(&self, other: Datapack, event: impl Fn(u64) + Copy) -> MergedResult<Datapack> {
-		let mut child = self.child.clone();
-		for value in other.child {
-			let namespace = match child.get(&value) {
-				Some(original) => original.merge(value, event)?,
-				None => value,
-			};
+	pub fn merge(
+		&self,
+		other: Datapack,
+		context: &mut MergeContext,
+		event: impl Fn(u64) + Copy,
+	) -> MergedResult<Datapack> {
+		let local_path = PathBuf::default();
+		let other = other.drop_files(&context.rules);
+
+		let mut result = Datapack::new(
+			&self.location,
+			&self.name,
+			self.child.clone(),
+			HashSet::default(),
+		);
+		result.assets = self.assets.clone();
+		let folders = vec![(DATA_FOLDER, other.child), (ASSETS_FOLDER, other.assets)];
+		for (folder, namespaces) in folders {
+			let result_namespaces = result.namespaces_mut(folder);
+			for value in namespaces {
+				// Namespaces with different children are not equal so they have to be looked up by name instead
+				let namespace = match take_namespace(result_namespaces, &value.name) {
+					Some(original) => {
+						let path = local_path.join(folder).join(&value.name);
+						original.merge(value, path, context, event)?
+					}
+					None => value,
+				};
 
-			child.replace(namespace);
+				result_namespaces.insert(namespace);
+			}
 		}
 
 		let mut files = self.files.clone();
 		for value in other.files {
 			let script = match files.get(&value) {
-				Some(original) => original.merge(value, event)?,
+				Some(original) => {
+					let path = local_path.join(&value.name);
+					original.merge(value, path, context, event)?
+				}
 				None => value,
 			};
 
 			files.replace(script);
 		}
 
-		let result = Datapack::new(&self.location, &self.name, child, files);
+		result.files = files;
 		Ok(result)
 	}
 
+	/// Remove every files that match `MergeRule::Drop`
+	fn drop_files(self, rules: &MergeRules) -> Datapack {
+		let files = self.flatten();
+		let is_dropped = |path: &PathBuf| rules.find(path) == Some(MergeRule::Drop);
+		if !files.keys().any(is_dropped) {
+			return self;
+		}
+
+		let kept: Vec<(PathBuf, Vec<u8>)> = files
+			.into_iter()
+			.filter(|(path, _)| !is_dropped(path))
+			.map(|(path, data)| (path, data.to_vec()))
+			.collect();
+		let mut result = Datapack::new(
+			&self.location,
+			&self.name,
+			HashSet::default(),
+			HashSet::default(),
+		);
+		for (path, data) in kept {
+			result.insert_file(&path, data);
+		}
+		result
+	}
+
+	/// `pack.mcmeta` is merged field by field, every other files at datapack root are generic
+	fn get_script_kind(name: &str) -> ScriptKind {
+		if name == PACK_META {
+			ScriptKind::PackMeta
+		} else {
+			ScriptKind::Generic
+		}
+	}
+
+	/// Namespaces inside `folder`, either `data` or `assets`
+	fn namespaces_mut(&mut self, folder: &str) -> &mut HashSet<Namespace> {
+		if folder == ASSETS_FOLDER {
+			&mut self.assets
+		} else {
+			&mut self.child
+		}
+	}
+
+	/// Insert `data` as a file at `path` relative to datapack root, every missing directories will be created
+	pub fn insert_file(&mut self, path: &Path, data: Vec<u8>) {
+		let components: Vec<String> = path.iter().map(os_str_to_string).collect();
+		match components.as_slice() {
+			[folder, name, rest @ ..]
+				if (folder == DATA_FOLDER || folder == ASSETS_FOLDER) && !rest.is_empty() =>
+			{
+				let origin = self.name.clone();
+				let namespaces = self.namespaces_mut(folder);
+				let mut namespace = take_namespace(namespaces, name)
+					.unwrap_or_else(|| Namespace::new(name, HashSet::default()));
+				namespace.insert(rest, data, &origin);
+				namespaces.insert(namespace);
+			}
+			_ => {
+				let kind = match components.as_slice() {
+					[name] => Datapack::get_script_kind(name),
+					_ => ScriptKind::Generic,
+				};
+				Script::insert_into(&mut self.files, &components, data, kind, &self.name)
+			}
+		}
+	}
+
+	/// Move values of `minecraft:load` function tag into a wrapper function named `load/<name>`,
+	/// `name` is this datapack's entry from `function_names()`
+	///
+	/// After merging, `minecraft:load` will only contain wrapper functions sorted by datapack priority.
+	/// This make initialisation order explicit and easy to audit.
+	///
+	/// Optional entries cannot be called from a function without failing when they are missing,
+	/// so they stay inside the tag right after the wrapper function.
+	///
+	/// `loaded` holds functions already called by wrappers of lower priority datapacks, they are skipped
+	/// so a function shared by several datapacks still only runs once. Datapacks must be wrapped from lowest to highest priority.
+	pub fn wrap_load(
+		mut self,
+		name: &str,
+		loaded: &mut LoadedFunctions,
+	) -> Result<Datapack, TreeError> {
+		for &(tag_path, folder) in LOAD_TAGS.iter() {
+			let load_tag = Path::new(tag_path);
+			let tag: Tag = match self.flatten().get(load_tag) {
+				Some(data) => serde_json::from_slice(data)
+					.map_err(|error| TreeError::Json(error, tag_path.to_string()))?,
+				None => continue,
+			};
+
+			let function = format!("load/{}", name);
+			let mut body = format!(
+				"# Load functions of '{}', generated by Centrosome\n",
+				self.name
+			);
+			let mut values = vec![TagValue::Id(format!("{}:{}", WRAPPER_NAMESPACE, function))];
+			for value in tag.values {
+				if !loaded.insert((folder, value.id().to_string())) {
+					continue;
+				}
+
+				if value.is_required() {
+					body.push_str(&format!("function {}\n", value.id()));
+				} else {
+					values.push(value);
+				}
+			}
+
+			let wrapper = Tag {
+				replace: tag.replace,
+				values,
+			};
+			let data = serde_json::to_vec_pretty(&wrapper)
+				.map_err(|error| TreeError::Json(error, tag_path.to_string()))?;
+
+			let function_path = Path::new("data")
+				.join(WRAPPER_NAMESPACE)
+				.join(folder)
+				.join(format!("{}.mcfunction", function));
+			self.insert_file(&function_path, body.into_bytes());
+			self.insert_file(load_tag, data);
+		}
+
+		Ok(self)
+	}
+
 	/// Because `Datapack` doesn't have the same data structure as the one implementing `DataTree`.
 	/// It cannot implement that trait itself so this function mimick `DataTree`'s compile() function
 	pub fn compile(
 		&self,
-		output_location: impl Into<PathBuf>,
-		options: &FileOptions,
+		sink: &mut impl OutputSink,
 		event: impl Fn(u64) + Copy,
 	) -> CompiledResult<()> {
-		let output_location = output_location.into();
-		let writer = File::create(&output_location)?;
-		let mut zip = ZipWriter::new(writer);
 		let local_path = PathBuf::default();
 
-		for namespace in &self.child {
-			let path = local_path.join("data").join(&namespace.name);
-			namespace.compile(path, &mut zip, options, event)?;
+		// Every entries are written in order of their path so the same datapack always compile into the same zip
+		let mut entries: Vec<RootEntry> = self
+			.folders()
+			.iter()
+			.map(|(folder, namespaces)| RootEntry::Folder(folder, namespaces))
+			.collect();
+		entries.extend(Script::sorted(&self.files).into_iter().map(RootEntry::File));
+		entries.sort_by_key(|entry| entry.name());
+
+		for entry in entries {
+			match entry {
+				RootEntry::Folder(folder, namespaces) => {
+					let mut namespaces: Vec<&Namespace> = namespaces.iter().collect();
+					namespaces.sort_by(|a, b| a.name.cmp(&b.name));
+					for namespace in namespaces {
+						let path = local_path.join(folder).join(&namespace.name);
+						namespace.compile(path, sink, event)?;
+					}
+				}
+				RootEntry::File(file) => {
+					let path = local_path.join(&file.name);
+					file.compile(path, sink, event)?;
+				}
+			}
+		}
+
+		sink.finish()?;
+
+		Ok(())
+	}
+
+	/// Because `Datapack` doesn't have the same data structure as the one implementing `DataTree`.
+	/// It cannot implement that trait itself so this function mimick `DataTree`'s flatten() function
+	pub fn flatten(&self) -> FileMap<'_> {
+		let mut files = FileMap::new();
+		let local_path = PathBuf::default();
+
+		for (folder, namespaces) in self.folders() {
+			for namespace in namespaces {
+				let path = local_path.join(folder).join(&namespace.name);
+				namespace.flatten(path, &mut files);
+			}
 		}
 
 		for file in &self.files {
 			let path = local_path.join(&file.name);
-			file.compile(path, &mut zip, options, event)?;
+			file.flatten(path, &mut files);
+		}
+
+		files
+	}
+
+	/// Namespaces of this datapack along with the folder they are in
+	fn folders(&self) -> [(&'static str, &HashSet<Namespace>); 2] {
+		[(DATA_FOLDER, &self.child), (ASSETS_FOLDER, &self.assets)]
+	}
+
+	/// Check that `pack.mcmeta`, tags and every other JSON files inside this datapack can be decoded
+	pub fn validate(&self) -> Vec<TreeError> {
+		let files = self.flatten();
+		let mut errors = Vec::new();
+
+		let pack_mcmeta = Path::new(PACK_META);
+		if !files.contains_key(pack_mcmeta) {
+			errors.push(TreeError::MissingFile(pack_mcmeta.to_path_buf()));
+		}
+		let has_folder = |folder| files.keys().any(|path| path.starts_with(folder));
+		if !has_folder(DATA_FOLDER) && !has_folder(ASSETS_FOLDER) {
+			errors.push(TreeError::MissingFile(PathBuf::from(DATA_FOLDER)));
 		}
 
-		zip.finish()?;
+		for (path, data) in files {
+			let result = if path == pack_mcmeta {
+				serde_json::from_slice::<PackMeta>(data).map(drop)
+			} else if is_tag(&path) {
+				serde_json::from_slice::<Tag>(data).map(drop)
+			} else if path.extension() == Some(OsStr::new("json")) {
+				serde_json::from_slice::<serde_json::Value>(data).map(drop)
+			} else {
+				continue;
+			};
 
-		Ok(())
+			if let Err(error) = result {
+				errors.push(TreeError::Json(error, path.display().to_string()));
+			}
+		}
+
+		errors
+	}
+}
+
+/// Take namespace named `name` out of `namespaces`, namespaces cannot be looked up directly because their children are part of equality
+fn take_namespace(namespaces: &mut HashSet<Namespace>, name: &str) -> Option<Namespace> {
+	let namespace = namespaces
+		.iter()
+		.find(|namespace| namespace.name == name)?
+		.clone();
+	namespaces.take(&namespace)
+}
+
+const PACK_META: &str = "pack.mcmeta";
+const DATA_FOLDER: &str = "data";
+const ASSETS_FOLDER: &str = "assets";
+const LOAD_TAG: &str = "data/minecraft/tags/functions/load.json";
+/// `minecraft:load` tags along with their functions folder, singular names are used since Minecraft 1.21
+const LOAD_TAGS: [(&str, &str); 2] = [
+	(LOAD_TAG, "functions"),
+	("data/minecraft/tags/function/load.json", "function"),
+];
+
+/// Functions already called from `minecraft:load` wrappers, keyed by functions folder they are called from
+pub type LoadedFunctions = HashSet<(&'static str, String)>;
+const WRAPPER_NAMESPACE: &str = "centrosome";
+
+/// Function name of every datapacks, keyed by datapack name
+pub type FunctionNames = HashMap<String, String>;
+
+/// Give every datapacks in `order` a function name that no other datapack has
+///
+/// Datapacks whose names convert to the same function name, such as `A B` and `a_b`,
+/// have their index in `order` appended so their functions never replace each other.
+pub fn function_names(order: &[String]) -> FunctionNames {
+	let names: Vec<String> = order.iter().map(|name| function_name(name)).collect();
+	let mut result = FunctionNames::new();
+	for (index, (datapack, name)) in order.iter().zip(&names).enumerate() {
+		let name = if names.iter().filter(|other| *other == name).count() > 1 {
+			format!("{}_{}", name, index)
+		} else {
+			name.clone()
+		};
+		result.insert(datapack.clone(), name);
+	}
+	result
+}
+
+/// Convert datapack name into a valid function name
+pub(super) fn function_name(name: &str) -> String {
+	name.trim_end_matches(".zip")
+		.chars()
+		.map(|character| match character.to_ascii_lowercase() {
+			character @ ('a'..='z' | '0'..='9' | '_' | '-' | '.') => character,
+			_ => '_',
+		})
+		.collect()
+}
+
+/// Tags are located at `data/<namespace>/tags/**`
+fn is_tag(path: &Path) -> bool {
+	path.iter().nth(2) == Some(OsStr::new("tags")) && path.extension() == Some(OsStr::new("json"))
+}
+
+/// Entry directly inside datapack root, either `data`/`assets` folder or a file such as `pack.mcmeta`
+enum RootEntry<'a> {
+	Folder(&'static str, &'a HashSet<Namespace>),
+	File(&'a Script),
+}
+
+impl<'a> RootEntry<'a> {
+	fn name(&self) -> &'a str {
+		match self {
+			RootEntry::Folder(folder, _) => folder,
+			RootEntry::File(file) => &file.name,
+		}
 	}
 }
 
@@ -152,6 +546,7 @@ impl From<DirEntry> for Datapack {
 			location,
 			name,
 			child,
+			assets: HashSet::default(),
 			files,
 		}
 	}
@@ -167,12 +562,12 @@ impl From<PathBuf> for Datapack {
 			location,
 			name,
 			child,
+			assets: HashSet::default(),
 			files,
 		}
 	}
 }
 
-use std::path::Path;
 impl From<&Path> for Datapack {
 	fn from(path: &Path) -> Datapack {
 		let name = os_str_to_string(path.as_os_str());
@@ -183,6 +578,7 @@ impl From<&Path> for Datapack {
 			location,
 			name,
 			child,
+			assets: HashSet::default(),
 			files,
 		}
 	}
@@ -199,6 +595,7 @@ impl From<DatapackLoader> for Datapack {
 			location,
 			name,
 			child,
+			assets: HashSet::default(),
 			files,
 		}
 	}
@@ -207,6 +604,10 @@ impl From<DatapackLoader> for Datapack {
 #[cfg(test)]
 mod tests {
 	use super::*;
+	use crate::datapack_loader::PackType;
+	use std::io::Write;
+	use zip::write::FileOptions;
+	use zip::ZipWriter;
 
 	#[test]
 	fn create_new_datapack() {
@@ -221,11 +622,105 @@ mod tests {
 				location: PathBuf::from("/tmp/random_location"),
 				name: String::from("random_location"),
 				child: HashSet::default(),
+				assets: HashSet::default(),
 				files: HashSet::default()
 			}
 		);
 	}
 
+	#[test]
+	fn wrap_load_functions() {
+		let mut datapack = Datapack::from(PathBuf::from("/tmp/Stand Arrow"));
+		datapack.insert_file(
+			Path::new(LOAD_TAG),
+			br##"
+			{
+				"values": [
+					"jojo:load",
+					{ "id": "jojo:requiem", "required": false },
+					"#jojo:stands"
+				]
+			}
+			"##
+			.to_vec(),
+		);
+
+		let datapack = datapack
+			.wrap_load("stand_arrow", &mut LoadedFunctions::new())
+			.unwrap();
+		let files = datapack.flatten();
+
+		let tag: Tag = serde_json::from_slice(files[Path::new(LOAD_TAG)]).unwrap();
+		assert_eq!(
+			tag.values,
+			vec![
+				TagValue::from("centrosome:load/stand_arrow"),
+				TagValue::Entry {
+					id: String::from("jojo:requiem"),
+					required: false,
+				},
+			]
+		);
+		assert_eq!(
+			files[Path::new("data/centrosome/functions/load/stand_arrow.mcfunction")],
+			&b"# Load functions of 'Stand Arrow', generated by Centrosome\nfunction jojo:load\nfunction #jojo:stands\n"[..]
+		);
+	}
+
+	#[test]
+	fn wrap_load_functions_only_once() {
+		let load = |name: &str, tag: &str, values: &str| {
+			let mut datapack = Datapack::from(PathBuf::from(name));
+			let data = format!(r#"{{ "values": {} }}"#, values);
+			datapack.insert_file(Path::new(tag), data.into_bytes());
+			datapack.with_origin(name)
+		};
+		let singular = "data/minecraft/tags/function/load.json";
+
+		let mut loaded = LoadedFunctions::new();
+		let jotaro = load("Jotaro", singular, r#"["lib:load", "jojo:star"]"#)
+			.wrap_load("jotaro", &mut loaded)
+			.unwrap();
+		let josuke = load("Josuke", singular, r#"["lib:load", "jojo:diamond"]"#)
+			.wrap_load("josuke", &mut loaded)
+			.unwrap();
+		let giorno = load("Giorno", LOAD_TAG, r#"["lib:load"]"#)
+			.wrap_load("giorno", &mut loaded)
+			.unwrap();
+
+		let files = jotaro.flatten();
+		assert_eq!(
+			files[Path::new("data/centrosome/function/load/jotaro.mcfunction")],
+			&b"# Load functions of 'Jotaro', generated by Centrosome\nfunction lib:load\nfunction jojo:star\n"[..]
+		);
+		let files = josuke.flatten();
+		assert_eq!(
+			files[Path::new("data/centrosome/function/load/josuke.mcfunction")],
+			&b"# Load functions of 'Josuke', generated by Centrosome\nfunction jojo:diamond\n"[..]
+		);
+		let tag: Tag = serde_json::from_slice(files[Path::new(singular)]).unwrap();
+		assert_eq!(tag.values, vec![TagValue::from("centrosome:load/josuke")]);
+
+		// Older datapacks are loaded from another folder so they still call shared functions
+		let files = giorno.flatten();
+		assert_eq!(
+			files[Path::new("data/centrosome/functions/load/giorno.mcfunction")],
+			&b"# Load functions of 'Giorno', generated by Centrosome\nfunction lib:load\n"[..]
+		);
+	}
+
+	#[test]
+	fn function_names_stay_unique() {
+		let order: Vec<String> = ["A B", "rem.zip", "a_b"]
+			.iter()
+			.map(|name| name.to_string())
+			.collect();
+		let names = function_names(&order);
+		assert_eq!(names["A B"], "a_b_0");
+		assert_eq!(names["rem.zip"], "rem");
+		assert_eq!(names["a_b"], "a_b_2");
+	}
+
 	#[test]
 	fn create_new_datapack_from_path_buf() {
 		assert_eq!(
@@ -234,8 +729,182 @@ mod tests {
 				name: String::from("ZA_WARUDO"),
 				location: PathBuf::from("/tmp/ZA_WARUDO"),
 				child: HashSet::default(),
+				assets: HashSet::default(),
 				files: HashSet::default()
 			}
 		);
 	}
+
+	fn compile_to_zip(datapack: &Datapack, path: &Path) -> Vec<u8> {
+		let options = FileOptions::default().last_modified_time(zip::DateTime::default());
+		let mut sink = super::super::ZipSink::create(path, options).unwrap();
+		datapack.compile(&mut sink, |_| {}).unwrap();
+		std::fs::read(path).unwrap()
+	}
+
+	#[test]
+	fn compile_into_identical_zip() {
+		let files = [
+			"pack.mcmeta",
+			"data/rem/functions/tea.mcfunction",
+			"data/ram/functions/broom.mcfunction",
+			"data/rem/tags/functions/load.json",
+			"assets/emilia/lang/en_us.json",
+			"pack.png",
+		];
+
+		let mut forward = Datapack::from(PathBuf::from("/tmp/Subaru"));
+		let mut backward = Datapack::from(PathBuf::from("/tmp/Subaru"));
+		for file in &files {
+			forward.insert_file(Path::new(file), file.as_bytes().to_vec());
+		}
+		for file in files.iter().rev() {
+			backward.insert_file(Path::new(file), file.as_bytes().to_vec());
+		}
+
+		let directory = tempfile::tempdir().unwrap();
+		let forward = compile_to_zip(&forward, &directory.path().join("forward.zip"));
+		let backward = compile_to_zip(&backward, &directory.path().join("backward.zip"));
+		assert_eq!(forward, backward);
+
+		let mut zip = ZipArchive::new(std::io::Cursor::new(forward)).unwrap();
+		let names: Vec<String> = (0..zip.len())
+			.map(|index| zip.by_index(index).unwrap().name().to_string())
+			.collect();
+		let mut sorted = names.clone();
+		sorted.sort();
+		assert_eq!(names, sorted);
+	}
+
+	fn pack_with_meta(name: &str, meta: &[u8]) -> Datapack {
+		let mut datapack = Datapack::from(PathBuf::from(name));
+		datapack.insert_file(Path::new(PACK_META), meta.to_vec());
+		datapack.with_origin(name)
+	}
+
+	#[test]
+	fn merge_pack_meta() {
+		let stardust = pack_with_meta(
+			"stardust",
+			br#"
+			{
+				"pack": { "pack_format": 10, "description": "Jotaro" },
+				"filter": { "block": [{ "namespace": "dio" }] }
+			}
+			"#,
+		);
+		let diamond = pack_with_meta(
+			"diamond",
+			br#"
+			{
+				"pack": {
+					"pack_format": 12,
+					"description": "Josuke",
+					"supported_formats": [11, 15]
+				},
+				"filter": { "block": [{ "namespace": "dio" }, { "path": "kira/.*" }] }
+			}
+			"#,
+		);
+
+		let mut context = MergeContext::default();
+		let result = stardust.merge(diamond, &mut context, |_| {}).unwrap();
+		let files = result.flatten();
+		let meta: serde_json::Value = serde_json::from_slice(files[Path::new(PACK_META)]).unwrap();
+
+		assert_eq!(
+			meta,
+			serde_json::json!({
+				"pack": {
+					"pack_format": 12,
+					"description": "Merged datapack of stardust, diamond",
+					"supported_formats": [10, 15]
+				},
+				"filter": { "block": [{ "namespace": "dio" }, { "path": "kira/.*" }] }
+			})
+		);
+		assert_eq!(context.report.len(), 1);
+	}
+
+	#[test]
+	fn describe_merged_resource_pack() {
+		let gold = pack_with_meta(
+			"gold",
+			br#"{ "pack": { "pack_format": 15, "description": "" } }"#,
+		);
+		let silver = pack_with_meta(
+			"silver",
+			br#"{ "pack": { "pack_format": 15, "description": "" } }"#,
+		);
+
+		let mut context = MergeContext {
+			pack_type: PackType::Resource,
+			..MergeContext::default()
+		};
+		let result = gold.merge(silver, &mut context, |_| {}).unwrap();
+		let files = result.flatten();
+		let meta: serde_json::Value = serde_json::from_slice(files[Path::new(PACK_META)]).unwrap();
+
+		assert_eq!(
+			meta["pack"]["description"],
+			"Merged resource pack of gold, silver"
+		);
+	}
+
+	#[test]
+	fn reject_incompatible_pack_format() {
+		let phantom = pack_with_meta(
+			"phantom",
+			br#"{ "pack": { "pack_format": 10, "description": "", "supported_formats": [9, 10] } }"#,
+		);
+		let steel = pack_with_meta(
+			"steel",
+			br#"{ "pack": { "pack_format": 15, "description": "" } }"#,
+		);
+
+		match phantom.merge(steel, &mut MergeContext::default(), |_| {}) {
+			Err(TreeError::IncompatibleFormat(pack, format, range)) => {
+				assert_eq!((pack.as_str(), format, range), ("phantom", 15, (9, 10)))
+			}
+			other => panic!("Unexpected result: {:?}", other.map(|_| ())),
+		}
+	}
+
+	#[test]
+	fn generate_from_zip_entries() {
+		let directory = tempfile::tempdir().unwrap();
+		let path = directory.path().join("Phantom Blood.zip");
+		let mut zip = ZipWriter::new(File::create(&path).unwrap());
+		let options = FileOptions::default();
+		// Zip without explicit directory entries
+		for (name, data) in &[
+			("pack.mcmeta", "{}"),
+			("data/jojo/functions/oraora.mcfunction", "say ora"),
+			("data/jojo/tags/functions/load.json", r#"{ "values": [] }"#),
+			("data/dio.txt", "ignored"),
+		] {
+			zip.start_file(*name, options).unwrap();
+			zip.write_all(data.as_bytes()).unwrap();
+		}
+		zip.finish().unwrap();
+
+		let mut problems = Vec::new();
+		let (datapack, size) = Datapack::generate(&path, &mut problems, |_| {}).unwrap();
+		let files = datapack.flatten();
+
+		assert!(problems.is_empty());
+		assert_eq!(size, 25);
+		assert_eq!(
+			files.keys().collect::<Vec<_>>(),
+			vec![
+				Path::new("data/jojo/functions/oraora.mcfunction"),
+				Path::new("data/jojo/tags/functions/load.json"),
+				Path::new("pack.mcmeta"),
+			]
+		);
+		assert_eq!(
+			files[Path::new("data/jojo/functions/oraora.mcfunction")],
+			b"say ora"
+		);
+	}
 }
@@ -1,61 +1,260 @@
 mod data_structure;
 mod datapack;
 mod namespace;
+mod report;
+mod rules;
 mod script;
+mod sink;
 
-use data_structure::Tag;
-pub use datapack::Datapack;
+use data_structure::{
+	union_json, Advancement, Atlas, Lang, LootTable, PackMeta, Sounds, Tag, TagValue,
+};
+use datapack::function_name;
+pub use datapack::{function_names, Datapack, FunctionNames, LoadedFunctions};
 use namespace::Namespace;
+pub use report::{MergeReport, Strategy};
+pub use rules::{MergeRule, MergeRules};
 use script::Script;
+pub use sink::{DirectorySink, OutputSink, ZipSink};
 
 type GeneratedResult<T> = Result<(T, u64), TreeError>;
 type MergedResult<T> = Result<T, TreeError>;
 type CompiledResult<T> = Result<T, TreeError>;
 
-use std::fs::{DirEntry, File};
-use zip::write::FileOptions;
-use zip::ZipWriter;
+use crate::datapack_loader::PackType;
+use std::collections::BTreeMap;
+use std::fs::DirEntry;
 /// A trait for handling recursive structure of file system
 trait DataTree {
 	/// Walk through files and directories and return encoded version of it
 	///
+	/// Files that cannot be read are skipped and pushed into `problems` instead of failing the whole data tree
+	///
 	/// `event` will run when it found a file and will have that file's size as argument
 	fn generate(
 		entry: DirEntry,
 		kind: ScriptKind,
+		problems: &mut Vec<TreeError>,
 		event: impl Fn(u64) + Copy,
 	) -> GeneratedResult<Self>
 	where
 		Self: Sized;
 	/// Merge two files or directories together
 	///
+	/// `path` is location of this data tree relative to datapack root, it is used to report conflicts into `context`
+	///
 	/// `event` will run when it found a file and will have that file's size as argument
-	fn merge(&self, other: Self, event: impl Fn(u64) + Copy) -> MergedResult<Self>
+	fn merge(
+		&self,
+		other: Self,
+		path: impl Into<PathBuf>,
+		context: &mut MergeContext,
+		event: impl Fn(u64) + Copy,
+	) -> MergedResult<Self>
 	where
 		Self: Sized;
-	/// Compile the data tree down into a single zip file
+	/// Write the data tree into `sink`, such as a zip file or a directory
 	///
 	/// `event` will run when it found a file and will have that file's size as argument
 	fn compile(
 		&self,
 		path: impl Into<PathBuf>,
-		zip: &mut ZipWriter<File>,
-		options: &FileOptions,
+		sink: &mut impl OutputSink,
 		event: impl Fn(u64) + Copy,
 	) -> CompiledResult<()>;
+	/// Collect every files inside the data tree into `files`, keyed by their path
+	fn flatten<'a>(&'a self, path: impl Into<PathBuf>, files: &mut FileMap<'a>);
+}
+
+/// Files of a data tree sorted by their path
+pub type FileMap<'a> = BTreeMap<PathBuf, &'a [u8]>;
+
+/// State shared between every merges of a single merging process
+#[derive(Debug, Default)]
+pub struct MergeContext {
+	pub report: MergeReport,
+	/// Keep every conflicting functions and call them in priority order instead of keeping only the winner
+	pub chain_functions: bool,
+	/// Per-path rules that take precedence over how each `ScriptKind` is merged
+	pub rules: MergeRules,
+	/// Name of the core datapack, used by `MergeRule::KeepCore`. Default to the highest priority datapack
+	pub core: Option<String>,
+	/// Problems that should fail the merge once every datapacks were merged
+	pub errors: Vec<TreeError>,
+	/// Treat every problems that were resolved automatically as errors
+	pub strict: bool,
+	/// Unique function name of every datapacks, from `function_names()`
+	pub function_names: FunctionNames,
+	/// Kind of packs being merged, used to describe the merged pack
+	pub pack_type: PackType,
+	/// Datapacks whose `pack.mcmeta` were merged, credited in merged datapack's description
+	credits: Vec<String>,
+}
+
+impl MergeContext {
+	/// Print `error` and carry on, or fail the merge later in strict mode
+	pub fn warn(&mut self, error: TreeError) {
+		if self.strict {
+			self.errors.push(error);
+		} else {
+			eprintln!("{}", error);
+		}
+	}
+
+	/// Conflict that was resolved by discarding one of the files, it is only an error in strict mode
+	fn unresolved(&mut self, error: TreeError) {
+		if self.strict {
+			self.errors.push(error);
+		}
+	}
+
+	/// Function name of datapack `origin`, it is converted from its name if it isn't in `function_names`
+	fn function_name(&self, origin: &str) -> String {
+		self.function_names
+			.get(origin)
+			.cloned()
+			.unwrap_or_else(|| function_name(origin))
+	}
+
+	/// Add `pack` to the credits and return description of merged datapack
+	fn credit(&mut self, packs: &[&str]) -> String {
+		for pack in packs {
+			if !self.credits.iter().any(|credit| credit == pack) {
+				self.credits.push(pack.to_string());
+			}
+		}
+		format!(
+			"Merged {} of {}",
+			self.pack_type.noun(),
+			self.credits.join(", ")
+		)
+	}
 }
 
 /// Possible type of file inside `Namespace`
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
 pub enum ScriptKind {
 	Tag,
+	PackMeta,
+	Function,
+	Advancement,
+	Recipe,
+	LootTable,
+	Predicate,
+	ItemModifier,
+	Structure,
+	Dimension,
+	DimensionType,
+	/// `None` is the `worldgen` directory itself or unknown folder inside it
+	Worldgen(Option<WorldgenKind>),
+	Lang,
+	Sounds,
+	Atlas,
+	Model,
+	Texture,
 	Generic,
+	#[default]
 	None,
 }
 
-impl Default for ScriptKind {
-	fn default() -> ScriptKind {
-		ScriptKind::None
+impl ScriptKind {
+	/// Kind of `name` inside a directory of this kind, only folders inside `worldgen` have their own kind
+	pub fn nested(self, name: &str) -> ScriptKind {
+		match self {
+			ScriptKind::Worldgen(None) => ScriptKind::Worldgen(WorldgenKind::from_name(name)),
+			kind => kind,
+		}
+	}
+
+	pub fn name(self) -> &'static str {
+		match self {
+			ScriptKind::Tag => "tag",
+			ScriptKind::PackMeta => "metadata",
+			ScriptKind::Function => "function",
+			ScriptKind::Advancement => "advancement",
+			ScriptKind::Recipe => "recipe",
+			ScriptKind::LootTable => "loot table",
+			ScriptKind::Predicate => "predicate",
+			ScriptKind::ItemModifier => "item modifier",
+			ScriptKind::Structure => "structure",
+			ScriptKind::Dimension => "dimension",
+			ScriptKind::DimensionType => "dimension type",
+			ScriptKind::Worldgen(Some(kind)) => kind.name(),
+			ScriptKind::Worldgen(None) => "worldgen",
+			ScriptKind::Lang => "lang",
+			ScriptKind::Sounds => "sounds",
+			ScriptKind::Atlas => "atlas",
+			ScriptKind::Model => "model",
+			ScriptKind::Texture => "texture",
+			ScriptKind::Generic => "file",
+			ScriptKind::None => "unknown",
+		}
+	}
+}
+
+/// Registries inside `worldgen` folder of a namespace
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WorldgenKind {
+	Biome,
+	ConfiguredCarver,
+	ConfiguredFeature,
+	DensityFunction,
+	FlatLevelGeneratorPreset,
+	MultiNoiseBiomeSourceParameterList,
+	Noise,
+	NoiseSettings,
+	PlacedFeature,
+	ProcessorList,
+	Structure,
+	StructureSet,
+	TemplatePool,
+	WorldPreset,
+}
+
+impl WorldgenKind {
+	const ALL: [WorldgenKind; 14] = [
+		WorldgenKind::Biome,
+		WorldgenKind::ConfiguredCarver,
+		WorldgenKind::ConfiguredFeature,
+		WorldgenKind::DensityFunction,
+		WorldgenKind::FlatLevelGeneratorPreset,
+		WorldgenKind::MultiNoiseBiomeSourceParameterList,
+		WorldgenKind::Noise,
+		WorldgenKind::NoiseSettings,
+		WorldgenKind::PlacedFeature,
+		WorldgenKind::ProcessorList,
+		WorldgenKind::Structure,
+		WorldgenKind::StructureSet,
+		WorldgenKind::TemplatePool,
+		WorldgenKind::WorldPreset,
+	];
+
+	fn from_name(name: &str) -> Option<WorldgenKind> {
+		WorldgenKind::ALL
+			.iter()
+			.copied()
+			.find(|kind| kind.name().trim_start_matches("worldgen/") == name)
+	}
+
+	pub fn name(self) -> &'static str {
+		match self {
+			WorldgenKind::Biome => "worldgen/biome",
+			WorldgenKind::ConfiguredCarver => "worldgen/configured_carver",
+			WorldgenKind::ConfiguredFeature => "worldgen/configured_feature",
+			WorldgenKind::DensityFunction => "worldgen/density_function",
+			WorldgenKind::FlatLevelGeneratorPreset => "worldgen/flat_level_generator_preset",
+			WorldgenKind::MultiNoiseBiomeSourceParameterList => {
+				"worldgen/multi_noise_biome_source_parameter_list"
+			}
+			WorldgenKind::Noise => "worldgen/noise",
+			WorldgenKind::NoiseSettings => "worldgen/noise_settings",
+			WorldgenKind::PlacedFeature => "worldgen/placed_feature",
+			WorldgenKind::ProcessorList => "worldgen/processor_list",
+			WorldgenKind::Structure => "worldgen/structure",
+			WorldgenKind::StructureSet => "worldgen/structure_set",
+			WorldgenKind::TemplatePool => "worldgen/template_pool",
+			WorldgenKind::WorldPreset => "worldgen/world_preset",
+		}
 	}
 }
 
@@ -78,7 +277,12 @@ pub enum TreeError {
 	FileInNamespace(PathBuf),
 	FileInDatapack(PathBuf),
 	UnknownFormat(String),
-	MismatchType(String, String),
+	MismatchType(ScriptKind, String, String),
+	MissingFile(PathBuf),
+	IncompatibleFormat(String, u32, (u32, u32)),
+	RuleViolation(PathBuf, String, String),
+	Overridden(PathBuf, String, String),
+	Fallback(PathBuf, String, Error),
 }
 
 use std::fmt;
@@ -101,12 +305,45 @@ impl fmt::Display for TreeError {
 			TreeError::UnknownFormat(source) => {
 				write!(f, "'{}' contained unknown format", source.cyan())
 			}
-			TreeError::MismatchType(source, other) => write!(
+			TreeError::MismatchType(kind, source, other) => write!(
 				f,
-				"'{}' and '{}' somehow have different type in merging progress",
+				"{} '{}' and '{}' somehow have different type in merging progress",
+				kind.name(),
 				source.cyan(),
 				other.cyan()
 			),
+			TreeError::MissingFile(source) => {
+				write!(f, "'{}' is missing", source.display().to_string().cyan())
+			}
+			TreeError::Overridden(path, original, other) => write!(
+				f,
+				"'{}' from '{}' was overridden by '{}'",
+				path.display().to_string().cyan(),
+				original,
+				other
+			),
+			TreeError::Fallback(path, pack, error) => write!(
+				f,
+				"'{}' from '{}' cannot be decoded: {}",
+				path.display().to_string().cyan(),
+				pack,
+				error
+			),
+			TreeError::RuleViolation(path, original, other) => write!(
+				f,
+				"'{}' is defined by both '{}' and '{}' but its merge rule is 'error'",
+				path.display().to_string().cyan(),
+				original,
+				other
+			),
+			TreeError::IncompatibleFormat(pack, format, (min, max)) => write!(
+				f,
+				"'{}' only supports pack format {} to {} but merged datapack uses pack format {}",
+				pack.cyan(),
+				min,
+				max,
+				format
+			),
 		}
 	}
 }
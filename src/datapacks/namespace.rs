@@ -1,10 +1,11 @@
 use super::{
-	CompiledResult, DataTree, GeneratedResult, MergedResult, Script, ScriptKind, TreeError,
+	CompiledResult, DataTree, FileMap, GeneratedResult, MergeContext, MergedResult, OutputSink,
+	Script, ScriptKind, TreeError,
 };
 use crate::utils::os_str_to_string;
 use std::collections::HashSet;
 
-/// Namespace represent a directory directly inside `/data` folder in a datapack
+/// Namespace represent a directory directly inside `/data` folder in a datapack or `/assets` folder in a resource pack
 #[derive(Debug, Clone, Eq)]
 pub struct Namespace {
 	pub name: String,
@@ -12,29 +13,53 @@ pub struct Namespace {
 }
 
 impl Namespace {
-	fn new(name: impl Into<String>, child: HashSet<Script>) -> Namespace {
+	pub fn new(name: impl Into<String>, child: HashSet<Script>) -> Namespace {
 		let name = name.into();
 		Namespace { name, child }
 	}
 
 	/// Inside namespace, folders will be split into "functions", "advancements", "tags" and etc.  
 	/// This function will convert name of those folders into `ScriptKind`
+	///
+	/// Singular names are used since Minecraft 1.21 so both of them are accepted.
+	/// Folders of resource pack don't share any name with datapack so they are listed here as well.
 	fn get_script_kind(name: &str) -> ScriptKind {
 		match name {
 			"tags" => ScriptKind::Tag,
+			"functions" | "function" => ScriptKind::Function,
+			"advancements" | "advancement" => ScriptKind::Advancement,
+			"recipes" | "recipe" => ScriptKind::Recipe,
+			"loot_tables" | "loot_table" => ScriptKind::LootTable,
+			"predicates" | "predicate" => ScriptKind::Predicate,
+			"item_modifiers" | "item_modifier" => ScriptKind::ItemModifier,
+			"structures" | "structure" => ScriptKind::Structure,
+			"dimension" => ScriptKind::Dimension,
+			"dimension_type" => ScriptKind::DimensionType,
+			"worldgen" => ScriptKind::Worldgen(None),
+			"lang" => ScriptKind::Lang,
+			"atlases" => ScriptKind::Atlas,
+			"models" => ScriptKind::Model,
+			"textures" => ScriptKind::Texture,
+			_ => ScriptKind::Generic,
+		}
+	}
+
+	/// Kind of file directly inside namespace, only resource pack's `sounds.json` has its own kind
+	fn get_file_kind(name: &str) -> ScriptKind {
+		match name {
+			"sounds.json" => ScriptKind::Sounds,
 			_ => ScriptKind::Generic,
 		}
 	}
 }
 
-use std::fs::{DirEntry, File};
+use std::fs::DirEntry;
 use std::path::PathBuf;
-use zip::write::FileOptions;
-use zip::ZipWriter;
 impl DataTree for Namespace {
 	fn generate(
 		entry: DirEntry,
 		_kind: ScriptKind,
+		problems: &mut Vec<TreeError>,
 		event: impl Fn(u64) + Copy,
 	) -> GeneratedResult<Namespace> {
 		if entry.metadata()?.is_dir() {
@@ -44,22 +69,23 @@ impl DataTree for Namespace {
 				let entry: DirEntry = entry?;
 
 				if entry.metadata()?.is_file() {
-					match Script::generate(entry, ScriptKind::Generic, event) {
+					let kind = Namespace::get_file_kind(&os_str_to_string(entry.file_name()));
+					match Script::generate(entry, kind, problems, event) {
 						Ok((script, child_size)) => {
 							child.insert(script);
 							size += child_size;
 						}
-						Err(error) => eprintln!("Unable to decode: {}", error),
+						Err(error) => problems.push(error),
 					}
 				} else {
 					let name = os_str_to_string(&entry.file_name());
 					let kind = Namespace::get_script_kind(&name);
-					match Script::generate(entry, kind, event) {
+					match Script::generate(entry, kind, problems, event) {
 						Ok((script, child_size)) => {
 							child.insert(script);
 							size += child_size;
 						}
-						Err(error) => eprintln!("Unable to decode: {}", error),
+						Err(error) => problems.push(error),
 					}
 				}
 			}
@@ -72,11 +98,21 @@ impl DataTree for Namespace {
 		}
 	}
 
-	fn merge(&self, other: Namespace, event: impl Fn(u64) + Copy) -> MergedResult<Namespace> {
+	fn merge(
+		&self,
+		other: Namespace,
+		path: impl Into<PathBuf>,
+		context: &mut MergeContext,
+		event: impl Fn(u64) + Copy,
+	) -> MergedResult<Namespace> {
+		let path = path.into();
 		let mut child: HashSet<Script> = self.child.clone();
 		for value in other.child {
 			let script = match child.get(&value) {
-				Some(original) => original.merge(value, event)?,
+				Some(original) => {
+					let path = path.join(&value.name);
+					original.merge(value, path, context, event)?
+				}
 				None => value,
 			};
 			child.replace(script);
@@ -91,19 +127,49 @@ impl DataTree for Namespace {
 	fn compile(
 		&self,
 		path: impl Into<PathBuf>,
-		zip: &mut ZipWriter<File>,
-		options: &FileOptions,
+		sink: &mut impl OutputSink,
 		event: impl Fn(u64) + Copy,
 	) -> CompiledResult<()> {
 		let path = path.into();
-		zip.add_directory_from_path(&path, *options)?;
+		sink.add_directory(&path)?;
 
-		for script in &self.child {
+		for script in Script::sorted(&self.child) {
 			let path = path.join(&script.name);
-			script.compile(path, zip, options, event)?;
+			script.compile(path, sink, event)?;
 		}
 		Ok(())
 	}
+
+	fn flatten<'a>(&'a self, path: impl Into<PathBuf>, files: &mut FileMap<'a>) {
+		let path = path.into();
+		for script in &self.child {
+			script.flatten(path.join(&script.name), files);
+		}
+	}
+}
+
+impl Namespace {
+	/// Insert `data` as a file at `path` relative to this namespace
+	pub fn insert(&mut self, path: &[String], data: Vec<u8>, origin: &str) {
+		let kind = match path {
+			[file] => Namespace::get_file_kind(file),
+			[category, ..] => Namespace::get_script_kind(category),
+			[] => return,
+		};
+		Script::insert_into(&mut self.child, path, data, kind, origin);
+	}
+
+	/// Mark every scripts inside this namespace as coming from `origin` datapack
+	pub fn set_origin(&mut self, origin: &str) {
+		self.child = self
+			.child
+			.drain()
+			.map(|mut script| {
+				script.set_origin(origin);
+				script
+			})
+			.collect();
+	}
 }
 
 impl PartialEq for Namespace {
@@ -130,7 +196,7 @@ impl From<DirEntry> for Namespace {
 
 #[cfg(test)]
 mod tests {
-	use super::super::FileType;
+	use super::super::{FileType, WorldgenKind};
 	use super::*;
 
 	#[test]
@@ -164,6 +230,30 @@ mod tests {
 		assert_eq!(Namespace::get_script_kind("tags"), ScriptKind::Tag);
 	}
 
+	#[test]
+	fn get_script_kind_categories() {
+		assert_eq!(
+			Namespace::get_script_kind("functions"),
+			ScriptKind::Function
+		);
+		assert_eq!(
+			Namespace::get_script_kind("loot_table"),
+			ScriptKind::LootTable
+		);
+		assert_eq!(
+			Namespace::get_script_kind("worldgen").nested("noise_settings"),
+			ScriptKind::Worldgen(Some(WorldgenKind::NoiseSettings))
+		);
+	}
+
+	#[test]
+	fn get_script_kind_resource_pack() {
+		assert_eq!(Namespace::get_script_kind("lang"), ScriptKind::Lang);
+		assert_eq!(Namespace::get_script_kind("textures"), ScriptKind::Texture);
+		assert_eq!(Namespace::get_file_kind("sounds.json"), ScriptKind::Sounds);
+		assert_eq!(Namespace::get_file_kind("readme.txt"), ScriptKind::Generic);
+	}
+
 	#[test]
 	fn get_script_kind_generic() {
 		assert_eq!(
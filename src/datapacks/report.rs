@@ -0,0 +1,293 @@
+use super::ScriptKind;
+use colored::*;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// How a conflict between two datapacks was resolved
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Strategy {
+	/// Higher priority datapack replace the whole file
+	Override,
+	/// Content of both files were combined
+	Append,
+	/// Higher priority tag has `replace: true` so values from lower priority tags were discarded
+	Replace,
+	/// One of the file cannot be decoded so the other one was kept as-is
+	Fallback,
+	/// Both functions were kept and called one after another in priority order
+	Chain,
+}
+
+impl Strategy {
+	fn name(self) -> &'static str {
+		match self {
+			Strategy::Override => "override",
+			Strategy::Append => "append",
+			Strategy::Replace => "replace",
+			Strategy::Fallback => "fallback",
+			Strategy::Chain => "chain",
+		}
+	}
+}
+
+/// A single resource that was defined by more than one datapack
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+	pub path: String,
+	/// Kind of resource such as "function" or "worldgen/biome"
+	pub kind: &'static str,
+	/// Datapacks that define this resource, sorted from lowest to highest priority
+	pub packs: Vec<String>,
+	/// Datapack whose file end up in the output, `None` if the files were combined
+	pub winner: Option<String>,
+	pub strategy: Strategy,
+	/// Problems found while resolving this conflict that may need manual review
+	#[serde(skip_serializing_if = "Vec::is_empty")]
+	pub notes: Vec<String>,
+}
+
+/// An entry inside datapacks directory that was not considered a datapack
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Rejected {
+	pub path: String,
+	/// Short name of the reason such as "missing-pack-meta" or "corrupt-zip"
+	pub reason: &'static str,
+	pub message: String,
+}
+
+/// Every conflicts found while merging datapacks, sorted by their path
+#[derive(Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct MergeReport {
+	conflicts: BTreeMap<String, Conflict>,
+	/// Entries that were rejected while looking for datapacks, only written to JSON report
+	rejected: Vec<Rejected>,
+}
+
+impl MergeReport {
+	/// Record conflict between `original` and `other` datapack at `path`
+	///
+	/// Conflicts on the same path are folded into a single entry so it will list every datapacks involved.
+	pub fn record(
+		&mut self,
+		path: &Path,
+		kind: ScriptKind,
+		original: &str,
+		other: &str,
+		winner: Option<&str>,
+		strategy: Strategy,
+	) {
+		let path = path.display().to_string();
+		let conflict = self
+			.conflicts
+			.entry(path.clone())
+			.or_insert_with(|| Conflict {
+				path,
+				kind: kind.name(),
+				packs: Vec::new(),
+				winner: None,
+				strategy,
+				notes: Vec::new(),
+			});
+
+		for pack in &[original, other] {
+			if !conflict.packs.iter().any(|value| value == pack) {
+				conflict.packs.push(pack.to_string());
+			}
+		}
+		conflict.winner = winner.map(String::from);
+		conflict.strategy = strategy;
+	}
+
+	/// Attach `note` to conflict at `path`, it must be recorded first
+	pub fn note(&mut self, path: &Path, note: impl Into<String>) {
+		if let Some(conflict) = self.conflicts.get_mut(&path.display().to_string()) {
+			conflict.notes.push(note.into());
+		}
+	}
+
+	/// Record that `path` was not considered a datapack because of `reason`
+	pub fn reject(&mut self, path: &Path, reason: &'static str, message: impl Into<String>) {
+		self.rejected.push(Rejected {
+			path: path.display().to_string(),
+			reason,
+			message: message.into(),
+		});
+	}
+
+	pub fn conflicts(&self) -> impl Iterator<Item = &Conflict> {
+		self.conflicts.values()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.conflicts.is_empty()
+	}
+
+	pub fn len(&self) -> usize {
+		self.conflicts.len()
+	}
+
+	pub fn to_json(&self) -> serde_json::Result<String> {
+		#[derive(Serialize)]
+		struct JsonReport<'a> {
+			conflicts: Vec<&'a Conflict>,
+			rejected: &'a [Rejected],
+		}
+
+		serde_json::to_string_pretty(&JsonReport {
+			conflicts: self.conflicts().collect(),
+			rejected: &self.rejected,
+		})
+	}
+
+	pub fn to_markdown(&self) -> String {
+		let mut result =
+			String::from("| Resource | Kind | Datapacks | Winner | Strategy | Notes |\n");
+		result.push_str("| --- | --- | --- | --- | --- | --- |\n");
+		for conflict in self.conflicts() {
+			result.push_str(&format!(
+				"| `{}` | {} | {} | {} | {} | {} |\n",
+				conflict.path,
+				conflict.kind,
+				conflict.packs.join(", "),
+				conflict.winner.as_deref().unwrap_or("-"),
+				conflict.strategy.name(),
+				conflict.notes.join("<br>")
+			));
+		}
+		result
+	}
+
+	/// Write this report to `path`, format is decided by file extension (`.md` for Markdown, JSON otherwise)
+	pub fn save(&self, path: &Path) -> io::Result<()> {
+		let is_markdown = path
+			.extension()
+			.map(|extension| extension == "md" || extension == "markdown")
+			.unwrap_or_default();
+
+		let content = if is_markdown {
+			self.to_markdown()
+		} else {
+			self.to_json()?
+		};
+
+		fs::write(path, content)
+	}
+}
+
+use std::fmt;
+impl fmt::Display for MergeReport {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		writeln!(f, "{} ({}):", "Conflicts".yellow().bold(), self.len())?;
+		for conflict in self.conflicts() {
+			let winner = match &conflict.winner {
+				Some(winner) => format!("→ {}", winner.green()),
+				None => String::from("→ combined"),
+			};
+			writeln!(
+				f,
+				"  {:<8} {} {} [{}] {}",
+				conflict.strategy.name().yellow(),
+				conflict.kind.dimmed(),
+				conflict.path.cyan(),
+				conflict.packs.join(" < "),
+				winner
+			)?;
+			for note in &conflict.notes {
+				writeln!(f, "           {} {}", "!".yellow().bold(), note)?;
+			}
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn fold_conflicts_on_same_path() {
+		let mut report = MergeReport::default();
+		let path = Path::new("data/jojo/functions/za_warudo.mcfunction");
+		report.record(
+			path,
+			ScriptKind::Function,
+			"jotaro",
+			"dio",
+			Some("dio"),
+			Strategy::Override,
+		);
+		report.record(
+			path,
+			ScriptKind::Function,
+			"dio",
+			"giorno",
+			Some("giorno"),
+			Strategy::Override,
+		);
+
+		let conflicts: Vec<&Conflict> = report.conflicts().collect();
+		assert_eq!(
+			conflicts,
+			vec![&Conflict {
+				path: path.display().to_string(),
+				kind: "function",
+				packs: vec![
+					String::from("jotaro"),
+					String::from("dio"),
+					String::from("giorno")
+				],
+				winner: Some(String::from("giorno")),
+				strategy: Strategy::Override,
+				notes: Vec::new(),
+			}]
+		);
+	}
+
+	#[test]
+	fn json_report_list_rejected_entries() {
+		let mut report = MergeReport::default();
+		report.reject(
+			Path::new("datapacks/subaru"),
+			"missing-pack-meta",
+			"missing pack.mcmeta",
+		);
+
+		let json: serde_json::Value = serde_json::from_str(&report.to_json().unwrap()).unwrap();
+		assert_eq!(
+			json,
+			serde_json::json!({
+				"conflicts": [],
+				"rejected": [{
+					"path": "datapacks/subaru",
+					"reason": "missing-pack-meta",
+					"message": "missing pack.mcmeta"
+				}]
+			})
+		);
+	}
+
+	#[test]
+	fn markdown_report() {
+		let mut report = MergeReport::default();
+		let path = Path::new("data/minecraft/tags/functions/load.json");
+		report.record(
+			path,
+			ScriptKind::Tag,
+			"saber",
+			"archer",
+			None,
+			Strategy::Append,
+		);
+
+		assert_eq!(
+			report.to_markdown(),
+			"| Resource | Kind | Datapacks | Winner | Strategy | Notes |\n\
+			 | --- | --- | --- | --- | --- | --- |\n\
+			 | `data/minecraft/tags/functions/load.json` | tag | saber, archer | - | append |  |\n"
+		);
+	}
+}
@@ -0,0 +1,110 @@
+use glob::{MatchOptions, Pattern, PatternError};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// How conflicts of resources matching a rule's pattern should be resolved
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum MergeRule {
+	/// Use the core datapack's file as-is, conflicts without the core datapack are merged as usual
+	KeepCore,
+	/// Combine both files even if they would be overridden otherwise
+	Union,
+	/// Fail the merge if more than one datapack define this resource
+	Error,
+	/// Remove this resource from the merged datapack
+	Drop,
+}
+
+impl MergeRule {
+	pub fn name(self) -> &'static str {
+		match self {
+			MergeRule::KeepCore => "keep-core",
+			MergeRule::Union => "union",
+			MergeRule::Error => "error",
+			MergeRule::Drop => "drop",
+		}
+	}
+
+	fn from_name(name: &str) -> Option<MergeRule> {
+		[
+			MergeRule::KeepCore,
+			MergeRule::Union,
+			MergeRule::Error,
+			MergeRule::Drop,
+		]
+		.iter()
+		.copied()
+		.find(|rule| rule.name() == name)
+	}
+}
+
+/// Merge rules sorted by their precedence, the first rule that match a path is used
+#[derive(Debug, Clone, Default)]
+pub struct MergeRules {
+	rules: Vec<(Pattern, MergeRule)>,
+}
+
+impl MergeRules {
+	/// Add `rule` for every paths matching `pattern`, `*` doesn't match across `/` but `**` does
+	pub fn push(&mut self, pattern: &str, rule: MergeRule) -> Result<(), PatternError> {
+		self.rules.push((Pattern::new(pattern)?, rule));
+		Ok(())
+	}
+
+	/// Parse rule in `<pattern>=<rule>` format such as `data/*/functions/**=keep-core`
+	pub fn parse(value: &str) -> Option<(&str, MergeRule)> {
+		let (pattern, rule) = value.rsplit_once('=')?;
+		Some((pattern, MergeRule::from_name(rule)?))
+	}
+
+	/// Rule of resource at `path` relative to datapack root
+	pub fn find(&self, path: &Path) -> Option<MergeRule> {
+		let options = MatchOptions {
+			require_literal_separator: true,
+			..MatchOptions::default()
+		};
+		self.rules
+			.iter()
+			.find(|(pattern, _)| pattern.matches_path_with(path, options))
+			.map(|(_, rule)| *rule)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn find_first_matching_rule() {
+		let mut rules = MergeRules::default();
+		rules
+			.push("data/*/functions/**", MergeRule::KeepCore)
+			.unwrap();
+		rules.push("data/minecraft/**", MergeRule::Union).unwrap();
+		rules.push("**/README*", MergeRule::Drop).unwrap();
+
+		let find = |path: &str| rules.find(Path::new(path));
+		assert_eq!(
+			find("data/rem/functions/lugnica/royal_election.mcfunction"),
+			Some(MergeRule::KeepCore)
+		);
+		assert_eq!(
+			find("data/minecraft/tags/functions/load.json"),
+			Some(MergeRule::Union)
+		);
+		assert_eq!(find("README.md"), Some(MergeRule::Drop));
+		assert_eq!(find("data/rem/README.txt"), Some(MergeRule::Drop));
+		assert_eq!(find("data/rem/recipes/mabeast.json"), None);
+	}
+
+	#[test]
+	fn parse_rule() {
+		assert_eq!(
+			MergeRules::parse("data/*/recipes/**=error"),
+			Some(("data/*/recipes/**", MergeRule::Error))
+		);
+		assert_eq!(MergeRules::parse("data/*/recipes/**=explode"), None);
+		assert_eq!(MergeRules::parse("data/*/recipes/**"), None);
+	}
+}
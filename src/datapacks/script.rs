@@ -1,10 +1,14 @@
 use super::{
-	CompiledResult, DataTree, FileType, GeneratedResult, MergedResult, ScriptKind, Tag, TreeError,
+	union_json, Advancement, Atlas, CompiledResult, DataTree, FileMap, FileType, GeneratedResult,
+	Lang, LootTable, MergeContext, MergeRule, MergedResult, OutputSink, PackMeta, ScriptKind,
+	Sounds, Strategy, Tag, TreeError,
 };
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use serde_json as js;
 use serde_json::Result as JsResult;
 use std::collections::HashSet;
+use std::ffi::OsStr;
 use std::fs;
 use std::hash::{Hash, Hasher};
 use std::io;
@@ -18,6 +22,8 @@ pub struct Script {
 	child: HashSet<Script>,
 	kind: ScriptKind,
 	file_type: FileType,
+	/// Name of the datapack this script came from
+	origin: String,
 }
 
 impl Script {
@@ -33,12 +39,588 @@ impl Script {
 			child,
 			kind,
 			file_type,
+			origin: String::default(),
 		}
 	}
 
+	/// Mark this script and every scripts inside it as coming from `origin` datapack
+	pub fn set_origin(&mut self, origin: &str) {
+		self.origin = origin.to_string();
+		self.child = self
+			.child
+			.drain()
+			.map(|mut script| {
+				script.set_origin(origin);
+				script
+			})
+			.collect();
+	}
+
+	/// Scripts ordered by name, so compiled output doesn't depend on the iteration order of `HashSet`
+	pub fn sorted(scripts: &HashSet<Script>) -> Vec<&Script> {
+		let mut scripts: Vec<&Script> = scripts.iter().collect();
+		scripts.sort_by(|a, b| a.name.cmp(&b.name));
+		scripts
+	}
+
+	/// Insert `data` as a file at `path` inside `scripts`, every missing directories along the way will be created
+	pub fn insert_into(
+		scripts: &mut HashSet<Script>,
+		path: &[String],
+		data: Vec<u8>,
+		kind: ScriptKind,
+		origin: &str,
+	) {
+		let (name, rest) = match path.split_first() {
+			Some(value) => value,
+			None => return,
+		};
+
+		if rest.is_empty() {
+			let mut script = Script::new(name, HashSet::default(), kind, FileType::File(data));
+			script.origin = origin.to_string();
+			scripts.replace(script);
+		} else {
+			let mut directory = Script::new(name, HashSet::default(), kind, FileType::Directory);
+			directory.origin = origin.to_string();
+			let mut directory = scripts.take(&directory).unwrap_or(directory);
+			let child_kind = kind.nested(&rest[0]);
+			Script::insert_into(&mut directory.child, rest, data, child_kind, origin);
+			scripts.insert(directory);
+		}
+	}
+
+	/// Record conflict between this script and `other` into merge report
+	fn record(
+		&self,
+		path: &Path,
+		other: &Script,
+		winner: &Script,
+		strategy: Strategy,
+		context: &mut MergeContext,
+	) {
+		context.report.record(
+			path,
+			self.kind,
+			&self.origin,
+			&other.origin,
+			Some(&winner.origin),
+			strategy,
+		);
+	}
+
+	/// Keep `winner` and throw the other file away, which fails the merge in strict mode
+	fn override_with(
+		&self,
+		path: &Path,
+		other: &Script,
+		winner: &Script,
+		context: &mut MergeContext,
+	) {
+		self.record(path, other, winner, Strategy::Override, context);
+		let dropped = if winner.origin == other.origin {
+			self
+		} else {
+			other
+		};
+		context.unresolved(TreeError::Overridden(
+			path.to_path_buf(),
+			dropped.origin.clone(),
+			winner.origin.clone(),
+		));
+	}
+
+	/// Keep `core` file as the `keep-core` rule asked, this conflict is resolved so it doesn't fail strict merges
+	fn keep_core_file(
+		&self,
+		path: &Path,
+		other: &Script,
+		core: &Script,
+		context: &mut MergeContext,
+	) {
+		self.record(path, other, core, Strategy::Override, context);
+		context.report.note(path, "resolved by 'keep-core' rule");
+	}
+
+	/// Merge two files depending on their `ScriptKind`
+	fn merge_by_kind(
+		&self,
+		data: &[u8],
+		other: Script,
+		path: &Path,
+		context: &mut MergeContext,
+		event: impl Fn(u64) + Copy,
+	) -> MergedResult<Script> {
+		match self.kind {
+			ScriptKind::Tag => self.merge_tag(data, other, path, context, event),
+			ScriptKind::PackMeta => self.merge_pack_meta(data, other, path, context, event),
+			ScriptKind::Advancement if is_json(path) => {
+				self.merge_advancement(data, other, path, context, event)
+			}
+			ScriptKind::LootTable if is_json(path) => {
+				self.merge_loot_table(data, other, path, context, event)
+			}
+			ScriptKind::Lang if is_json(path) => self.merge_lang(data, other, path, context, event),
+			ScriptKind::Sounds => self.merge_sounds(data, other, path, context, event),
+			ScriptKind::Atlas if is_json(path) => {
+				self.merge_atlas(data, other, path, context, event)
+			}
+			ScriptKind::None => Err(TreeError::UnknownFormat(self.name.clone())),
+			// Every other resources are replaced by the higher priority datapack
+			_ => {
+				if self.file_type != other.file_type {
+					self.override_with(path, &other, &other, context);
+				}
+				Ok(other)
+			}
+		}
+	}
+
+	/// Keep the core datapack's file if it is involved in this conflict, otherwise merge as usual
+	fn keep_core(
+		&self,
+		data: Vec<u8>,
+		other: Script,
+		path: &Path,
+		context: &mut MergeContext,
+		event: impl Fn(u64) + Copy,
+	) -> MergedResult<Script> {
+		let core = context.core.as_deref();
+		if self.file_type == other.file_type {
+			Ok(other)
+		} else if core == Some(other.origin.as_str()) {
+			self.keep_core_file(path, &other, &other, context);
+			Ok(other)
+		} else if core == Some(self.origin.as_str()) {
+			self.keep_core_file(path, &other, self, context);
+			Ok(self.clone())
+		} else {
+			self.merge_by_kind(&data, other, path, context, event)
+		}
+	}
+
+	/// Combine both files even if they would be overridden otherwise
+	///
+	/// Functions are run one after another and JSON files are deeply merged.
+	/// Files that cannot be combined are overridden with a note in the report.
+	fn union(
+		&self,
+		data: &[u8],
+		other: Script,
+		path: &Path,
+		context: &mut MergeContext,
+		event: impl Fn(u64) + Copy,
+	) -> MergedResult<Script> {
+		match self.kind {
+			ScriptKind::Tag
+			| ScriptKind::PackMeta
+			| ScriptKind::Advancement
+			| ScriptKind::LootTable
+			| ScriptKind::Lang
+			| ScriptKind::Sounds
+			| ScriptKind::Atlas => return self.merge_by_kind(data, other, path, context, event),
+			_ => (),
+		}
+
+		let other_data = self.other_data(&other)?;
+		if data == other_data.as_slice() {
+			return Ok(other);
+		}
+
+		if path.extension() == Some(OsStr::new("mcfunction")) {
+			let mut result = data.to_vec();
+			if !result.ends_with(b"\n") {
+				result.push(b'\n');
+			}
+			result.extend(other_data);
+			return Ok(self.finish_union(result, other, path, context, event));
+		}
+
+		if is_json(path) {
+			let (original, prototype): (js::Value, js::Value) =
+				match self.decode_pair(data, &other, path, context)? {
+					Ok(pair) => pair,
+					Err(fallback) => return Ok(fallback),
+				};
+			let result = union_json(original, prototype);
+			let result = Script::encode(&result).map_err(|error| (error, self.name.clone()))?;
+			return Ok(self.finish_union(result, other, path, context, event));
+		}
+
+		self.override_with(path, &other, &other, context);
+		context.report.note(
+			path,
+			"file cannot be combined, only the higher priority one was kept",
+		);
+		Ok(other)
+	}
+
+	fn finish_union(
+		&self,
+		data: Vec<u8>,
+		other: Script,
+		path: &Path,
+		context: &mut MergeContext,
+		event: impl Fn(u64) + Copy,
+	) -> Script {
+		context.report.record(
+			path,
+			self.kind,
+			&self.origin,
+			&other.origin,
+			None,
+			Strategy::Append,
+		);
+		Script::with_data(other, data, event)
+	}
+
+	/// Data of `other` file, fail if `other` is a directory
+	fn other_data(&self, other: &Script) -> MergedResult<Vec<u8>> {
+		match &other.file_type {
+			FileType::File(data) => Ok(data.clone()),
+			FileType::Directory => Err(TreeError::MismatchType(
+				self.kind,
+				self.name.clone(),
+				other.name.clone(),
+			)),
+		}
+	}
+
+	/// Create merged file from `other` with new `data`
+	fn with_data(other: Script, data: Vec<u8>, event: impl Fn(u64) + Copy) -> Script {
+		event(data.len() as u64);
+		let mut result = Script::new(other.name, other.child, other.kind, FileType::File(data));
+		result.origin = other.origin;
+		result
+	}
+
+	/// Decode both this file and `other` file as `T`
+	///
+	/// If either of them cannot be decoded, the other one is recorded as fallback and returned as `Err` instead.
+	fn decode_pair<T: DeserializeOwned>(
+		&self,
+		data: &[u8],
+		other: &Script,
+		path: &Path,
+		context: &mut MergeContext,
+	) -> MergedResult<Result<(T, T), Script>> {
+		let original: T = match Script::decode(data) {
+			Ok(original) => original,
+			Err(error) => {
+				self.record(path, other, other, Strategy::Fallback, context);
+				context.unresolved(TreeError::Fallback(
+					path.to_path_buf(),
+					self.origin.clone(),
+					error,
+				));
+				return Ok(Err(other.clone()));
+			}
+		};
+		let prototype: T = match Script::decode(&self.other_data(other)?) {
+			Ok(prototype) => prototype,
+			Err(error) => {
+				self.record(path, other, self, Strategy::Fallback, context);
+				context.unresolved(TreeError::Fallback(
+					path.to_path_buf(),
+					other.origin.clone(),
+					error,
+				));
+				return Ok(Err(self.clone()));
+			}
+		};
+
+		Ok(Ok((original, prototype)))
+	}
+
+	fn merge_tag(
+		&self,
+		data: &[u8],
+		other: Script,
+		path: &Path,
+		context: &mut MergeContext,
+		event: impl Fn(u64) + Copy,
+	) -> MergedResult<Script> {
+		let (original, prototype): (Tag, Tag) =
+			match self.decode_pair(data, &other, path, context)? {
+				Ok(pair) => pair,
+				Err(fallback) => return Ok(fallback),
+			};
+
+		let strategy = if prototype.is_replace() {
+			Strategy::Replace
+		} else {
+			Strategy::Append
+		};
+		let result = original.merge(prototype);
+		let data = match Script::encode(&result) {
+			Ok(x) => x,
+			// Return `other` immediately if there are json error
+			// Such as "Invalid syntax"
+			Err(error) => {
+				self.record(path, &other, &other, Strategy::Fallback, context);
+				context.unresolved(TreeError::Fallback(
+					path.to_path_buf(),
+					self.origin.clone(),
+					error.into(),
+				));
+				return Ok(other);
+			}
+		};
+
+		match strategy {
+			Strategy::Replace => self.record(path, &other, &other, strategy, context),
+			_ => {
+				context
+					.report
+					.record(path, self.kind, &self.origin, &other.origin, None, strategy)
+			}
+		}
+
+		Ok(Script::with_data(other, data, event))
+	}
+
+	/// Append pools of lower priority loot table to `other`'s pools, report pools that cannot be reconciled
+	fn merge_loot_table(
+		&self,
+		data: &[u8],
+		other: Script,
+		path: &Path,
+		context: &mut MergeContext,
+		event: impl Fn(u64) + Copy,
+	) -> MergedResult<Script> {
+		let (original, prototype): (LootTable, LootTable) =
+			match self.decode_pair(data, &other, path, context)? {
+				Ok(pair) => pair,
+				Err(fallback) => return Ok(fallback),
+			};
+
+		if let (Some(kind), Some(other_kind)) = (&original.kind, &prototype.kind) {
+			if kind != other_kind {
+				let note = format!(
+					"loot table type is different ('{}' and '{}'), only the higher priority one was kept",
+					kind, other_kind
+				);
+				self.override_with(path, &other, &other, context);
+				context.report.note(path, note);
+				return Ok(other);
+			}
+		}
+
+		let (result, notes) = original.merge(prototype);
+		self.finish_structural_merge(&result, notes, other, path, context, event)
+	}
+
+	/// Combine criteria and requirements of both advancements, report fields that they disagree on
+	fn merge_advancement(
+		&self,
+		data: &[u8],
+		other: Script,
+		path: &Path,
+		context: &mut MergeContext,
+		event: impl Fn(u64) + Copy,
+	) -> MergedResult<Script> {
+		let (original, prototype): (Advancement, Advancement) =
+			match self.decode_pair(data, &other, path, context)? {
+				Ok(pair) => pair,
+				Err(fallback) => return Ok(fallback),
+			};
+
+		let (result, notes) = original.merge(prototype);
+		self.finish_structural_merge(&result, notes, other, path, context, event)
+	}
+
+	/// Merge language files key by key, keys that both files translate differently are reported
+	fn merge_lang(
+		&self,
+		data: &[u8],
+		other: Script,
+		path: &Path,
+		context: &mut MergeContext,
+		event: impl Fn(u64) + Copy,
+	) -> MergedResult<Script> {
+		let (original, prototype): (Lang, Lang) =
+			match self.decode_pair(data, &other, path, context)? {
+				Ok(pair) => pair,
+				Err(fallback) => return Ok(fallback),
+			};
+
+		let (result, notes) = original.merge(prototype);
+		self.finish_structural_merge(&result, notes, other, path, context, event)
+	}
+
+	/// Combine sound events of both `sounds.json`, sound lists of the same event are concatenated
+	fn merge_sounds(
+		&self,
+		data: &[u8],
+		other: Script,
+		path: &Path,
+		context: &mut MergeContext,
+		event: impl Fn(u64) + Copy,
+	) -> MergedResult<Script> {
+		let (original, prototype): (Sounds, Sounds) =
+			match self.decode_pair(data, &other, path, context)? {
+				Ok(pair) => pair,
+				Err(fallback) => return Ok(fallback),
+			};
+
+		let (result, notes) = original.merge(prototype);
+		self.finish_structural_merge(&result, notes, other, path, context, event)
+	}
+
+	/// Combine sources of both texture atlases
+	fn merge_atlas(
+		&self,
+		data: &[u8],
+		other: Script,
+		path: &Path,
+		context: &mut MergeContext,
+		event: impl Fn(u64) + Copy,
+	) -> MergedResult<Script> {
+		let (original, prototype): (Atlas, Atlas) =
+			match self.decode_pair(data, &other, path, context)? {
+				Ok(pair) => pair,
+				Err(fallback) => return Ok(fallback),
+			};
+
+		let (result, notes) = original.merge(prototype);
+		self.finish_structural_merge(&result, notes, other, path, context, event)
+	}
+
+	/// Encode combined `result` in place of `other` and report it along with its `notes`
+	fn finish_structural_merge<T: Serialize>(
+		&self,
+		result: &T,
+		notes: Vec<String>,
+		other: Script,
+		path: &Path,
+		context: &mut MergeContext,
+		event: impl Fn(u64) + Copy,
+	) -> MergedResult<Script> {
+		let data = Script::encode(result).map_err(|error| (error, self.name.clone()))?;
+		context.report.record(
+			path,
+			self.kind,
+			&self.origin,
+			&other.origin,
+			None,
+			Strategy::Append,
+		);
+		for note in notes {
+			context.report.note(path, note);
+		}
+
+		Ok(Script::with_data(other, data, event))
+	}
+
+	/// Merge `pack.mcmeta` of two datapacks, fail if their pack formats are incompatible
+	fn merge_pack_meta(
+		&self,
+		data: &[u8],
+		other: Script,
+		path: &Path,
+		context: &mut MergeContext,
+		event: impl Fn(u64) + Copy,
+	) -> MergedResult<Script> {
+		let (original, prototype): (PackMeta, PackMeta) =
+			match self.decode_pair(data, &other, path, context)? {
+				Ok(pair) => pair,
+				Err(fallback) => return Ok(fallback),
+			};
+
+		let pack_format = original.pack.pack_format.max(prototype.pack.pack_format);
+		let original_formats = original.pack.supported_formats;
+		let mut result = original.merge(prototype).map_err(|range| {
+			let pack = if original_formats == Some(range) {
+				&self.origin
+			} else {
+				&other.origin
+			};
+			TreeError::IncompatibleFormat(pack.clone(), pack_format, range.bounds())
+		})?;
+		result.pack.description = context.credit(&[&self.origin, &other.origin]).into();
+
+		let data = Script::encode(&result).map_err(|error| (error, self.name.clone()))?;
+		context.report.record(
+			path,
+			self.kind,
+			&self.origin,
+			&other.origin,
+			None,
+			Strategy::Append,
+		);
+
+		Ok(Script::with_data(other, data, event))
+	}
+
+	/// Keep both functions under datapack-suffixed names such as `ns:foo__datapack`
+	/// and turn this function into a dispatcher that calls each of them in priority order.
+	///
+	/// Return every scripts that need to be placed next to this function.
+	fn chain_function(
+		&self,
+		other: Script,
+		path: &Path,
+		context: &mut MergeContext,
+		event: impl Fn(u64) + Copy,
+	) -> MergedResult<Vec<Script>> {
+		let data = match &self.file_type {
+			FileType::File(data) => data.clone(),
+			FileType::Directory => {
+				return self
+					.merge(other, path, context, event)
+					.map(|script| vec![script])
+			}
+		};
+		let other_data = self.other_data(&other)?;
+		if data == other_data {
+			return Ok(vec![other]);
+		}
+
+		let id = function_id(path).ok_or_else(|| TreeError::UnknownFormat(self.name.clone()))?;
+		let header = format!(
+			"# Call every '{}' functions in priority order, generated by Centrosome\n",
+			id
+		);
+
+		let mut scripts = Vec::new();
+		let mut body = if data.starts_with(header.as_bytes()) {
+			data
+		} else {
+			let suffix = context.function_name(&self.origin);
+			let (copy, copy_id) = self.renamed_function(&id, &suffix, data);
+			scripts.push(copy);
+			format!("{}function {}\n", header, copy_id).into_bytes()
+		};
+
+		let suffix = context.function_name(&other.origin);
+		let (copy, copy_id) = other.renamed_function(&id, &suffix, other_data);
+		body.extend(format!("function {}\n", copy_id).into_bytes());
+		scripts.push(copy);
+
+		context.report.record(
+			path,
+			self.kind,
+			&self.origin,
+			&other.origin,
+			None,
+			Strategy::Chain,
+		);
+		scripts.push(Script::with_data(other, body, event));
+		Ok(scripts)
+	}
+
+	/// Copy of this function renamed after its datapack's function name `suffix`, return along with its function id
+	fn renamed_function(&self, id: &str, suffix: &str, data: Vec<u8>) -> (Script, String) {
+		let stem = self.name.trim_end_matches(".mcfunction");
+		let name = format!("{}__{}.mcfunction", stem, suffix);
+		let mut script = Script::new(name, HashSet::default(), self.kind, FileType::File(data));
+		script.origin = self.origin.clone();
+		(script, format!("{}__{}", id, suffix))
+	}
+
 	/// Decode JSON data from slices
 	fn decode<'a, T: Deserialize<'a>>(data: &'a [u8]) -> io::Result<T> {
-		let result: T = js::from_slice(&data)?;
+		let result: T = js::from_slice(data)?;
 		Ok(result)
 	}
 
@@ -48,15 +630,37 @@ impl Script {
 	}
 }
 
-use std::fs::{DirEntry, File};
-use std::io::Write;
-use std::path::PathBuf;
-use zip::write::FileOptions;
-use zip::ZipWriter;
+fn is_json(path: &Path) -> bool {
+	path.extension() == Some(OsStr::new("json"))
+}
+
+/// Only `.mcfunction` files are functions, other files inside `functions` folder are ignored by Minecraft
+fn is_function(script: &Script, path: &Path) -> bool {
+	script.kind == ScriptKind::Function && path.extension() == Some(OsStr::new("mcfunction"))
+}
+
+/// Function id of function at `path` such as `namespace:path/to/function`
+fn function_id(path: &Path) -> Option<String> {
+	let components: Vec<String> = path
+		.with_extension("")
+		.iter()
+		.map(os_str_to_string)
+		.collect();
+	match components.as_slice() {
+		[_data, namespace, _functions, rest @ ..] if !rest.is_empty() => {
+			Some(format!("{}:{}", namespace, rest.join("/")))
+		}
+		_ => None,
+	}
+}
+
+use std::fs::DirEntry;
+use std::path::{Path, PathBuf};
 impl DataTree for Script {
 	fn generate(
 		entry: DirEntry,
 		kind: ScriptKind,
+		problems: &mut Vec<TreeError>,
 		event: impl Fn(u64) + Copy,
 	) -> GeneratedResult<Script> {
 		if entry.metadata()?.is_file() {
@@ -72,88 +676,84 @@ impl DataTree for Script {
 			let mut size = 0;
 			for entry in entry.path().read_dir()? {
 				let entry: DirEntry = entry?;
-				match Script::generate(entry, kind, event) {
+				let name = os_str_to_string(entry.file_name());
+				match Script::generate(entry, kind.nested(&name), problems, event) {
 					Ok((script, child_size)) => {
 						child.insert(script);
 						size += child_size;
 					}
-					Err(error) => eprintln!("Unable to decode: {}", error),
+					Err(error) => problems.push(error),
 				}
 			}
 
-			let name = os_str_to_string(&entry.file_name());
+			let name = os_str_to_string(entry.file_name());
 			let script = Script::new(name, child, kind, FileType::Directory);
 
 			Ok((script, size))
 		}
 	}
 
-	fn merge(&self, other: Script, event: impl Fn(u64) + Copy) -> MergedResult<Script> {
+	fn merge(
+		&self,
+		other: Script,
+		path: impl Into<PathBuf>,
+		context: &mut MergeContext,
+		event: impl Fn(u64) + Copy,
+	) -> MergedResult<Script> {
+		let path = path.into();
 		match self.file_type.clone() {
-			FileType::File(data) => {
-				match self.kind {
-					ScriptKind::Tag => {
-						let original: Tag = match Script::decode(&data) {
-							Ok(original) => original,
-							Err(_) => return Ok(other),
-						};
-						let prototype: io::Result<Tag> = match other.file_type.clone() {
-							FileType::File(data) => Script::decode(&data),
-							FileType::Directory => {
-								return Err(TreeError::MismatchType(
-									self.name.clone(),
-									other.name.clone(),
-								))
-							}
-						};
-
-						let mut prototype = match prototype {
-							Ok(prototype) => prototype,
-							Err(_) => return Ok(self.clone()),
-						};
-
-						let mut result = original;
-						result.values.append(&mut prototype.values);
-						let data = match Script::encode(&result) {
-							Ok(x) => x,
-							// Return `other` immediately if there are json error
-							// Such as "Invalid syntax"
-							Err(_error) => return Ok(other),
-						};
-						let size = data.len() as u64;
-
-						let name = other.name;
-						let child = other.child;
-						let kind = other.kind;
-						let file_type = FileType::File(data);
-
-						event(size);
-						let result = Script::new(name, child, kind, file_type);
-						Ok(result)
+			FileType::File(data) => match context.rules.find(&path) {
+				Some(MergeRule::KeepCore) => self.keep_core(data, other, &path, context, event),
+				Some(MergeRule::Union) => self.union(&data, other, &path, context, event),
+				Some(MergeRule::Error) => {
+					if self.file_type != other.file_type {
+						let error = TreeError::RuleViolation(
+							path.clone(),
+							self.origin.clone(),
+							other.origin.clone(),
+						);
+						context.errors.push(error);
+						self.record(&path, &other, &other, Strategy::Override, context);
 					}
-					ScriptKind::Generic => Ok(other),
-					ScriptKind::None => Err(TreeError::UnknownFormat(self.name.clone())),
+					Ok(other)
 				}
-			}
+				// Dropped files are removed before merging
+				Some(MergeRule::Drop) | None => {
+					self.merge_by_kind(&data, other, &path, context, event)
+				}
+			},
 			FileType::Directory => {
 				let mut child = self.child.clone();
 				for value in other.child {
-					let script = match child.get(&value) {
-						Some(original) => original.merge(value, event),
-						None => Ok(value),
+					let scripts = match child.get(&value) {
+						Some(original) => {
+							let path = path.join(&value.name);
+							let has_rule = context.rules.find(&path).is_some();
+							if context.chain_functions && !has_rule && is_function(&value, &path) {
+								original.chain_function(value, &path, context, event)
+							} else {
+								original
+									.merge(value, path, context, event)
+									.map(|script| vec![script])
+							}
+						}
+						None => Ok(vec![value]),
 					};
 
-					match script {
-						Ok(script) => {
-							child.replace(script);
+					match scripts {
+						Ok(scripts) => {
+							for script in scripts {
+								child.replace(script);
+							}
 						}
-						Err(error) => eprintln!("{}", error),
+						Err(error) => context.warn(error),
 					};
 				}
 				let name = other.name;
 				let kind = other.kind;
 				let file_type = other.file_type;
-				let result = Script::new(name, child, kind, file_type);
+				let mut result = Script::new(name, child, kind, file_type);
+				result.origin = other.origin;
 				Ok(result)
 			}
 		}
@@ -162,35 +762,47 @@ impl DataTree for Script {
 	fn compile(
 		&self,
 		path: impl Into<PathBuf>,
-		zip: &mut ZipWriter<File>,
-		options: &FileOptions,
+		sink: &mut impl OutputSink,
 		event: impl Fn(u64) + Copy,
 	) -> CompiledResult<()> {
 		let path: PathBuf = path.into();
 		
 		match &self.file_type {
 			FileType::Directory => {
-				zip.add_directory_from_path(&path, *options)?;
-				for script in &self.child {
+				sink.add_directory(&path)?;
+				for script in Script::sorted(&self.child) {
 					let child = path.join(&script.name);
-					script.compile(child, zip, options, event)?;
+					script.compile(child, sink, event)?;
 				}
 			}
 			FileType::File(data) => {
-				zip.start_file_from_path(&path, *options)?;
-				zip.write_all(&data)?;
+				sink.add_file(&path, data)?;
 				event(data.len() as u64);
 			}
 		};
 
 		Ok(())
 	}
+
+	fn flatten<'a>(&'a self, path: impl Into<PathBuf>, files: &mut FileMap<'a>) {
+		let path = path.into();
+		match &self.file_type {
+			FileType::Directory => {
+				for script in &self.child {
+					script.flatten(path.join(&script.name), files);
+				}
+			}
+			FileType::File(data) => {
+				files.insert(path, data);
+			}
+		}
+	}
 }
 
 use crate::utils::os_str_to_string;
 impl From<(DirEntry, ScriptKind)> for Script {
 	fn from((entry, kind): (DirEntry, ScriptKind)) -> Script {
-		let name = os_str_to_string(&entry.file_name());
+		let name = os_str_to_string(entry.file_name());
 		let child = HashSet::default();
 		let file_type = {
 			if entry.metadata().unwrap().is_file() {
@@ -204,6 +816,7 @@ impl From<(DirEntry, ScriptKind)> for Script {
 			child,
 			kind,
 			file_type,
+			origin: String::default(),
 		}
 	}
 }
@@ -233,6 +846,7 @@ impl fmt::Debug for Script {
 
 #[cfg(test)]
 mod tests {
+	use super::super::TagValue;
 	use super::*;
 
 	#[test]
@@ -248,7 +862,8 @@ mod tests {
 				name: String::from("hello_world"),
 				child: HashSet::default(),
 				kind: ScriptKind::Generic,
-				file_type: FileType::Directory
+				file_type: FileType::Directory,
+				origin: String::default()
 			}
 		);
 	}
@@ -274,14 +889,14 @@ mod tests {
 		let expect = Tag {
 			replace: None,
 			values: vec![
-				String::from("Jonathan Joestar"),
-				String::from("Joseph Joestar"),
-				String::from("Jotaro Kujo"),
-				String::from("Josuke Higashikata"),
-				String::from("Giorno Giovanna"),
-				String::from("Jolyne Cujoh"),
-				String::from("Johnny Joestar"),
-				String::from("Josuke Higashikata"),
+				TagValue::from("Jonathan Joestar"),
+				TagValue::from("Joseph Joestar"),
+				TagValue::from("Jotaro Kujo"),
+				TagValue::from("Josuke Higashikata"),
+				TagValue::from("Giorno Giovanna"),
+				TagValue::from("Jolyne Cujoh"),
+				TagValue::from("Johnny Joestar"),
+				TagValue::from("Josuke Higashikata"),
 			],
 		};
 
@@ -332,13 +947,11 @@ mod tests {
 				"Giorno Giovanna",
 				"Jolyne Cujoh",
 				"Johnny Joestar",
-				"Josuke Higashikata",
 				"Shirou Emiya",
 				"Saber",
 				"Rin Tohsaka",
 				"Archer",
 				"Sakura Matou",
-				"Sakura Matou",
 				"Rider",
 				"Illyasviel von Einzbern",
 				"Kirei Kotomine",
@@ -361,7 +974,10 @@ mod tests {
 			FileType::File(fate_data.to_vec()),
 		);
 
-		let value = jojo_script.merge(fate_script, |_| {}).unwrap();
+		let mut context = MergeContext::default();
+		let value = jojo_script
+			.merge(fate_script, "jojo", &mut context, |_| {})
+			.unwrap();
 		let expect = Script::new(
 			"fate",
 			HashSet::default(),
@@ -370,5 +986,729 @@ mod tests {
 		);
 
 		assert_eq!(value, expect);
+		assert_eq!(decode_tag(&value), decode_tag(&expect));
+	}
+
+	#[test]
+	fn merge_tags_in_priority_order() {
+		let fate_data = r#"{ "values": ["Saber", "Archer", "Lancer"] }"#.as_bytes();
+		let fate_zero_data = r#"{ "values": ["Lancer", "Saber", "Rider"] }"#.as_bytes();
+
+		let fate_script = Script::new(
+			"servants",
+			HashSet::default(),
+			ScriptKind::Tag,
+			FileType::File(fate_data.to_vec()),
+		);
+		let fate_zero_script = Script::new(
+			"servants",
+			HashSet::default(),
+			ScriptKind::Tag,
+			FileType::File(fate_zero_data.to_vec()),
+		);
+
+		let mut context = MergeContext::default();
+		let value = fate_script
+			.merge(fate_zero_script, "servants", &mut context, |_| {})
+			.unwrap();
+
+		assert_eq!(
+			decode_tag(&value).values,
+			vec![
+				TagValue::from("Saber"),
+				TagValue::from("Archer"),
+				TagValue::from("Lancer"),
+				TagValue::from("Rider"),
+			]
+		);
+	}
+
+	#[test]
+	fn merge_tags_with_replace() {
+		let fate_data = r#"{ "values": ["Saber", "Archer", "Lancer"] }"#.as_bytes();
+		let fate_grand_order_data = r#"
+		{
+			"replace": true,
+			"values": ["Mash Kyrielight", "Saber"]
+		}
+		"#
+		.as_bytes();
+
+		let fate_script = Script::new(
+			"servants",
+			HashSet::default(),
+			ScriptKind::Tag,
+			FileType::File(fate_data.to_vec()),
+		);
+		let mut fate_grand_order_script = Script::new(
+			"servants",
+			HashSet::default(),
+			ScriptKind::Tag,
+			FileType::File(fate_grand_order_data.to_vec()),
+		);
+		fate_grand_order_script.set_origin("fate_grand_order");
+
+		let mut context = MergeContext::default();
+		let value = fate_script
+			.merge(fate_grand_order_script, "servants", &mut context, |_| {})
+			.unwrap();
+
+		assert_eq!(
+			decode_tag(&value),
+			Tag {
+				replace: Some(true),
+				values: vec![TagValue::from("Mash Kyrielight"), TagValue::from("Saber")],
+			}
+		);
+
+		let conflict = context.report.conflicts().next().unwrap();
+		assert_eq!(conflict.strategy, Strategy::Replace);
+		assert_eq!(conflict.winner.as_deref(), Some("fate_grand_order"));
+	}
+
+	#[test]
+	fn keep_replace_after_merging() {
+		let original = Tag {
+			replace: Some(true),
+			values: vec![TagValue::from("Saber")],
+		};
+		let other = Tag {
+			replace: None,
+			values: vec![TagValue::from("Archer")],
+		};
+
+		assert_eq!(
+			original.merge(other),
+			Tag {
+				replace: Some(true),
+				values: vec![TagValue::from("Saber"), TagValue::from("Archer")],
+			}
+		);
+	}
+
+	#[test]
+	fn decode_optional_tag_entries() {
+		let data = r#"
+		{
+			"values": [
+				"Shirou Emiya",
+				{ "id": "Saber", "required": false },
+				{ "id": "Archer" }
+			]
+		}
+		"#;
+
+		let value: Tag = Script::decode(data.as_bytes()).unwrap();
+		let expect = Tag {
+			replace: None,
+			values: vec![
+				TagValue::from("Shirou Emiya"),
+				TagValue::Entry {
+					id: String::from("Saber"),
+					required: false,
+				},
+				TagValue::Entry {
+					id: String::from("Archer"),
+					required: true,
+				},
+			],
+		};
+		assert_eq!(value, expect);
+
+		let data = Script::encode(&value).unwrap();
+		let value: Tag = Script::decode(&data).unwrap();
+		assert_eq!(value, expect);
+	}
+
+	#[test]
+	fn merge_optional_tag_entries() {
+		let fate_data = r#"
+		{
+			"values": [
+				{ "id": "Saber", "required": false },
+				{ "id": "Rider", "required": false }
+			]
+		}
+		"#
+		.as_bytes();
+		let fate_zero_data = r#"
+		{
+			"values": [
+				"Saber",
+				{ "id": "Rider", "required": false },
+				{ "id": "Berserker", "required": false }
+			]
+		}
+		"#
+		.as_bytes();
+
+		let fate_script = Script::new(
+			"servants",
+			HashSet::default(),
+			ScriptKind::Tag,
+			FileType::File(fate_data.to_vec()),
+		);
+		let fate_zero_script = Script::new(
+			"servants",
+			HashSet::default(),
+			ScriptKind::Tag,
+			FileType::File(fate_zero_data.to_vec()),
+		);
+
+		let mut context = MergeContext::default();
+		let value = fate_script
+			.merge(fate_zero_script, "servants", &mut context, |_| {})
+			.unwrap();
+
+		assert_eq!(
+			decode_tag(&value).values,
+			vec![
+				TagValue::Entry {
+					id: String::from("Saber"),
+					required: true,
+				},
+				TagValue::Entry {
+					id: String::from("Rider"),
+					required: false,
+				},
+				TagValue::Entry {
+					id: String::from("Berserker"),
+					required: false,
+				},
+			]
+		);
+	}
+
+	fn decode_tag(script: &Script) -> Tag {
+		match &script.file_type {
+			FileType::File(data) => Script::decode(data).unwrap(),
+			FileType::Directory => panic!("'{}' is not a file", script.name),
+		}
+	}
+
+	/// File named `name` that comes from `origin` datapack
+	fn script_of(name: &str, kind: ScriptKind, origin: &str, data: impl AsRef<[u8]>) -> Script {
+		let file_type = FileType::File(data.as_ref().to_vec());
+		let mut script = Script::new(name, HashSet::default(), kind, file_type);
+		script.origin = origin.to_string();
+		script
+	}
+
+	fn functions_of(origin: &str, body: &[u8]) -> Script {
+		let kind = ScriptKind::Function;
+		let mut functions = Script::new("functions", HashSet::default(), kind, FileType::Directory);
+		functions.origin = origin.to_string();
+		functions
+			.child
+			.insert(script_of("summon.mcfunction", kind, origin, body));
+		functions
+	}
+
+	#[test]
+	fn chain_conflicting_functions() {
+		let mut context = MergeContext {
+			chain_functions: true,
+			..MergeContext::default()
+		};
+		let path = Path::new("data/fate");
+		let result = functions_of("Saber", b"say excalibur")
+			.merge(
+				functions_of("Archer", b"say unlimited blade works"),
+				path.join("functions"),
+				&mut context,
+				|_| {},
+			)
+			.unwrap()
+			.merge(
+				functions_of("Gilgamesh", b"say enuma elish"),
+				path.join("functions"),
+				&mut context,
+				|_| {},
+			)
+			.unwrap();
+
+		let mut files = FileMap::new();
+		result.flatten(path.join("functions"), &mut files);
+		let file = |name: &str| files[&path.join("functions").join(name)];
+
+		assert_eq!(file("summon__saber.mcfunction"), b"say excalibur");
+		assert_eq!(
+			file("summon__archer.mcfunction"),
+			b"say unlimited blade works"
+		);
+		assert_eq!(file("summon__gilgamesh.mcfunction"), b"say enuma elish");
+		assert_eq!(
+			file("summon.mcfunction"),
+			&b"# Call every 'fate:summon' functions in priority order, generated by Centrosome\n\
+			function fate:summon__saber\n\
+			function fate:summon__archer\n\
+			function fate:summon__gilgamesh\n"[..]
+		);
+		assert_eq!(files.len(), 4);
+	}
+
+	#[test]
+	fn merge_loot_table_pools() {
+		let rotten_flesh =
+			r#"{ "rolls": 1, "entries": [{ "type": "item", "name": "rotten_flesh" }] }"#;
+		let iron = r#"{ "rolls": 1, "entries": [{ "type": "item", "name": "iron_ingot" }] }"#;
+		let more_iron = r#"{ "rolls": 3, "entries": [{ "type": "item", "name": "iron_ingot" }] }"#;
+		let carrot = r#"{ "rolls": 1, "entries": [{ "type": "item", "name": "carrot" }] }"#;
+
+		let subaru = script_of(
+			"zombie.json",
+			ScriptKind::LootTable,
+			"Subaru",
+			format!(
+				r#"{{ "type": "entity", "pools": [{}, {}, {}] }}"#,
+				rotten_flesh, carrot, more_iron
+			),
+		);
+		let emilia = script_of(
+			"zombie.json",
+			ScriptKind::LootTable,
+			"Emilia",
+			format!(
+				r#"{{ "type": "entity", "pools": [{}, {}] }}"#,
+				rotten_flesh, iron
+			),
+		);
+
+		let mut context = MergeContext::default();
+		let path = Path::new("data/minecraft/loot_tables/entities/zombie.json");
+		let result = subaru.merge(emilia, path, &mut context, |_| {}).unwrap();
+
+		let mut files = FileMap::new();
+		result.flatten(path, &mut files);
+		let value: LootTable = Script::decode(files[path]).unwrap();
+		let pools: Vec<js::Value> = [rotten_flesh, iron, carrot]
+			.iter()
+			.map(|pool| js::from_str(pool).unwrap())
+			.collect();
+		assert_eq!(value.pools, pools);
+
+		let conflict = context.report.conflicts().next().unwrap();
+		assert_eq!(conflict.strategy, Strategy::Append);
+		assert_eq!(
+			conflict.notes,
+			vec![String::from("pool #3 has the same entries as higher priority pool #2 but different settings, only the higher priority one was kept")]
+		);
+	}
+
+	#[test]
+	fn merge_loot_table_keep_repeated_pools() {
+		let rotten_flesh =
+			r#"{ "rolls": 1, "entries": [{ "type": "item", "name": "rotten_flesh" }] }"#;
+		let iron = r#"{ "rolls": 1, "entries": [{ "type": "item", "name": "iron_ingot" }] }"#;
+
+		let subaru = script_of(
+			"zombie.json",
+			ScriptKind::LootTable,
+			"Subaru",
+			format!(
+				r#"{{ "type": "entity", "pools": [{}, {}] }}"#,
+				rotten_flesh, rotten_flesh
+			),
+		);
+		let emilia = script_of(
+			"zombie.json",
+			ScriptKind::LootTable,
+			"Emilia",
+			format!(r#"{{ "type": "entity", "pools": [{}] }}"#, iron),
+		);
+
+		let mut context = MergeContext::default();
+		let path = Path::new("data/minecraft/loot_tables/entities/zombie.json");
+		let result = subaru.merge(emilia, path, &mut context, |_| {}).unwrap();
+
+		let mut files = FileMap::new();
+		result.flatten(path, &mut files);
+		let value: LootTable = Script::decode(files[path]).unwrap();
+		let pools: Vec<js::Value> = [iron, rotten_flesh, rotten_flesh]
+			.iter()
+			.map(|pool| js::from_str(pool).unwrap())
+			.collect();
+		assert_eq!(value.pools, pools);
+	}
+
+	fn merge_resource(lower: Script, higher: Script, path: &Path) -> js::Value {
+		let mut context = MergeContext::default();
+		let result = lower.merge(higher, path, &mut context, |_| {}).unwrap();
+		let mut files = FileMap::new();
+		result.flatten(path, &mut files);
+		js::from_slice(files[path]).unwrap()
+	}
+
+	#[test]
+	fn merge_sounds_keep_lower_replace() {
+		let kind = ScriptKind::Sounds;
+		let lower = script_of(
+			"sounds.json",
+			kind,
+			"Ram",
+			r#"{ "block.note_block.bell": { "replace": true, "sounds": ["ram/bell"] } }"#,
+		);
+		let higher = script_of(
+			"sounds.json",
+			kind,
+			"Rem",
+			r#"{ "block.note_block.bell": { "sounds": ["rem/bell"] } }"#,
+		);
+		let path = Path::new("assets/minecraft/sounds.json");
+		assert_eq!(
+			merge_resource(lower, higher, path),
+			js::json!({
+				"block.note_block.bell": { "replace": true, "sounds": ["ram/bell", "rem/bell"] }
+			})
+		);
+	}
+
+	#[test]
+	fn merge_resource_pack_files() {
+		let kind = ScriptKind::Sounds;
+		let lower = script_of(
+			"sounds.json",
+			kind,
+			"Ram",
+			r#"{
+				"entity.rem.cry": { "sounds": ["rem/cry1"], "subtitle": "subtitles.rem.cry" },
+				"entity.ram.sigh": { "sounds": ["ram/sigh"] },
+				"music.roswaal": { "sounds": ["roswaal/old"] }
+			}"#,
+		);
+		let higher = script_of(
+			"sounds.json",
+			kind,
+			"Rem",
+			r#"{
+				"entity.rem.cry": { "sounds": ["rem/cry1", "rem/cry2"] },
+				"music.roswaal": { "replace": true, "sounds": ["roswaal/new"] }
+			}"#,
+		);
+		let path = Path::new("assets/rezero/sounds.json");
+		assert_eq!(
+			merge_resource(lower, higher, path),
+			js::json!({
+				"entity.rem.cry": { "sounds": ["rem/cry1", "rem/cry2"], "subtitle": "subtitles.rem.cry" },
+				"entity.ram.sigh": { "sounds": ["ram/sigh"] },
+				"music.roswaal": { "replace": true, "sounds": ["roswaal/new"] }
+			})
+		);
+
+		let kind = ScriptKind::Atlas;
+		let lower = script_of(
+			"blocks.json",
+			kind,
+			"Ram",
+			r#"{ "sources": [{ "type": "directory", "source": "block", "prefix": "block/" }] }"#,
+		);
+		let higher = script_of(
+			"blocks.json",
+			kind,
+			"Rem",
+			r#"{ "sources": [{ "type": "directory", "source": "rezero", "prefix": "rezero/" }] }"#,
+		);
+		let path = Path::new("assets/minecraft/atlases/blocks.json");
+		assert_eq!(
+			merge_resource(lower, higher, path),
+			js::json!({
+				"sources": [
+					{ "type": "directory", "source": "block", "prefix": "block/" },
+					{ "type": "directory", "source": "rezero", "prefix": "rezero/" }
+				]
+			})
+		);
+
+		let kind = ScriptKind::Lang;
+		let lower = script_of(
+			"en_us.json",
+			kind,
+			"Ram",
+			r#"{ "item.rezero.flail": "Flail", "item.rezero.broom": "Broom" }"#,
+		);
+		let higher = script_of(
+			"en_us.json",
+			kind,
+			"Rem",
+			r#"{ "item.rezero.flail": "Morning Star" }"#,
+		);
+		let path = Path::new("assets/rezero/lang/en_us.json");
+		assert_eq!(
+			merge_resource(lower, higher, path),
+			js::json!({ "item.rezero.flail": "Morning Star", "item.rezero.broom": "Broom" })
+		);
+	}
+
+	#[test]
+	fn merge_lang_key_by_key() {
+		let kind = ScriptKind::Lang;
+		let lower = script_of(
+			"en_us.json",
+			kind,
+			"Ram",
+			r#"{ "item.rezero.flail": "Flail", "item.rezero.broom": "Broom", "item.rezero.horn": "Horn" }"#,
+		);
+		let higher = script_of(
+			"en_us.json",
+			kind,
+			"Rem",
+			r#"{ "item.rezero.horn": "Horn", "item.rezero.flail": "Morning Star", "item.rezero.apple": "Apple" }"#,
+		);
+
+		let mut context = MergeContext::default();
+		let path = Path::new("assets/rezero/lang/en_us.json");
+		let result = lower.merge(higher, path, &mut context, |_| {}).unwrap();
+		let mut files = FileMap::new();
+		result.flatten(path, &mut files);
+
+		let data = String::from_utf8(files[path].to_vec()).unwrap();
+		let keys: Vec<usize> = [
+			"item.rezero.apple",
+			"item.rezero.broom",
+			"item.rezero.flail",
+			"item.rezero.horn",
+		]
+		.iter()
+		.map(|key| data.find(key).unwrap())
+		.collect();
+		assert!(keys.windows(2).all(|pair| pair[0] < pair[1]));
+		assert!(data.contains("Morning Star"));
+
+		let conflict = context.report.conflicts().next().unwrap();
+		assert_eq!(conflict.strategy, Strategy::Append);
+		assert_eq!(
+			conflict.notes,
+			vec![String::from(
+				"translation 'item.rezero.flail' is different, only the higher priority one was kept"
+			)]
+		);
+	}
+
+	#[test]
+	fn merge_shared_root_advancement() {
+		let kind = ScriptKind::Advancement;
+		let mut aqua = Script::new(
+			"root.json",
+			HashSet::default(),
+			kind,
+			FileType::File(
+				br#"{
+					"criteria": { "installed": { "trigger": "minecraft:tick" } },
+					"display": { "title": "Aqua" }
+				}"#
+				.to_vec(),
+			),
+		);
+		aqua.origin = String::from("Aqua");
+		let mut megumin = Script::new(
+			"root.json",
+			HashSet::default(),
+			kind,
+			FileType::File(
+				br#"{
+					"criteria": {
+						"installed": { "trigger": "minecraft:tick" },
+						"explosion": { "trigger": "minecraft:location" }
+					},
+					"requirements": [["explosion"]],
+					"display": { "title": "Megumin" }
+				}"#
+				.to_vec(),
+			),
+		);
+		megumin.origin = String::from("Megumin");
+
+		let mut context = MergeContext::default();
+		let path = Path::new("data/global/advancements/root.json");
+		let result = aqua.merge(megumin, path, &mut context, |_| {}).unwrap();
+
+		let mut files = FileMap::new();
+		result.flatten(path, &mut files);
+		let value: js::Value = js::from_slice(files[path]).unwrap();
+		assert_eq!(
+			value,
+			js::json!({
+				"criteria": {
+					"installed": { "trigger": "minecraft:tick" },
+					"explosion": { "trigger": "minecraft:location" }
+				},
+				"requirements": [["explosion"], ["installed"]],
+				"display": { "title": "Megumin" }
+			})
+		);
+
+		let conflict = context.report.conflicts().next().unwrap();
+		assert_eq!(
+			conflict.notes,
+			vec![String::from(
+				"'display' is different, only the higher priority one was kept"
+			)]
+		);
+	}
+
+	#[test]
+	fn merge_with_path_rules() {
+		let mut context = MergeContext {
+			core: Some(String::from("Saber")),
+			..MergeContext::default()
+		};
+		context
+			.rules
+			.push("data/*/functions/union/**", MergeRule::Union)
+			.unwrap();
+		context
+			.rules
+			.push("data/*/functions/core/**", MergeRule::KeepCore)
+			.unwrap();
+		context
+			.rules
+			.push("data/*/functions/**", MergeRule::Error)
+			.unwrap();
+
+		let data = |script: Script| match script.file_type {
+			FileType::File(data) => data,
+			FileType::Directory => panic!("Unexpected directory"),
+		};
+		let mut merge = |path: &str, original: &str, other: &str| {
+			let path = Path::new("data/fate/functions").join(path);
+			let kind = ScriptKind::Function;
+			let original = script_of("f.mcfunction", kind, original, "say original");
+			let other = script_of("f.mcfunction", kind, other, "say other");
+			data(original.merge(other, path, &mut context, |_| {}).unwrap())
+		};
+
+		assert_eq!(
+			merge("union/f.mcfunction", "Archer", "Lancer"),
+			b"say original\nsay other"
+		);
+		assert_eq!(
+			merge("core/f.mcfunction", "Saber", "Lancer"),
+			b"say original"
+		);
+		assert_eq!(merge("f.mcfunction", "Archer", "Lancer"), b"say other");
+
+		match context.errors.as_slice() {
+			[TreeError::RuleViolation(path, original, other)] => {
+				assert_eq!(path, Path::new("data/fate/functions/f.mcfunction"));
+				assert_eq!((original.as_str(), other.as_str()), ("Archer", "Lancer"));
+			}
+			errors => panic!("Unexpected errors: {:?}", errors),
+		}
+	}
+
+	#[test]
+	fn collect_problems_in_strict_mode() {
+		let mut context = MergeContext {
+			strict: true,
+			..MergeContext::default()
+		};
+		let kazuma = script_of(
+			"steal.mcfunction",
+			ScriptKind::Function,
+			"Kazuma",
+			b"say panties",
+		);
+		let chris = script_of(
+			"steal.mcfunction",
+			ScriptKind::Function,
+			"Chris",
+			b"say treasure",
+		);
+		let path = Path::new("data/konosuba/functions/steal.mcfunction");
+		kazuma.merge(chris, path, &mut context, |_| {}).unwrap();
+
+		let aqua = script_of("party.json", ScriptKind::Tag, "Aqua", b"{ invalid");
+		let darkness = script_of(
+			"party.json",
+			ScriptKind::Tag,
+			"Darkness",
+			br#"{ "values": [] }"#,
+		);
+		let path = Path::new("data/konosuba/tags/party.json");
+		aqua.merge(darkness, path, &mut context, |_| {}).unwrap();
+
+		match context.errors.as_slice() {
+			[TreeError::Overridden(_, original, other), TreeError::Fallback(_, pack, _)] => {
+				assert_eq!((original.as_str(), other.as_str()), ("Kazuma", "Chris"));
+				assert_eq!(pack, "Aqua");
+			}
+			errors => panic!("Unexpected errors: {:?}", errors),
+		}
+	}
+
+	fn strict_merge(
+		lower: Script,
+		higher: Script,
+		path: &Path,
+		core: Option<&str>,
+	) -> MergeContext {
+		let mut context = MergeContext {
+			strict: true,
+			core: core.map(String::from),
+			..MergeContext::default()
+		};
+		context
+			.rules
+			.push("**/keep/**", MergeRule::KeepCore)
+			.unwrap();
+		context.rules.push("**/union/**", MergeRule::Union).unwrap();
+		lower.merge(higher, path, &mut context, |_| {}).unwrap();
+		context
+	}
+
+	fn overridden_by(errors: &[TreeError]) -> Vec<(&str, &str)> {
+		errors
+			.iter()
+			.map(|error| match error {
+				TreeError::Overridden(_, original, other) => (original.as_str(), other.as_str()),
+				error => panic!("Unexpected error: {:?}", error),
+			})
+			.collect()
+	}
+
+	#[test]
+	fn strict_mode_flag_union_fallback() {
+		let kind = ScriptKind::Structure;
+		let lower = script_of("castle.nbt", kind, "Emilia", "castle");
+		let higher = script_of("castle.nbt", kind, "Puck", "spirit");
+		let path = Path::new("data/rezero/union/castle.nbt");
+		let errors = strict_merge(lower, higher, path, None).errors;
+		assert_eq!(overridden_by(&errors), vec![("Emilia", "Puck")]);
+	}
+
+	#[test]
+	fn strict_mode_flag_different_loot_table_type() {
+		let kind = ScriptKind::LootTable;
+		let lower = script_of(
+			"chest.json",
+			kind,
+			"Emilia",
+			r#"{ "type": "minecraft:chest", "pools": [] }"#,
+		);
+		let higher = script_of(
+			"chest.json",
+			kind,
+			"Puck",
+			r#"{ "type": "minecraft:block", "pools": [] }"#,
+		);
+		let path = Path::new("data/rezero/loot_tables/chest.json");
+		let errors = strict_merge(lower, higher, path, None).errors;
+		assert_eq!(overridden_by(&errors), vec![("Emilia", "Puck")]);
+	}
+
+	#[test]
+	fn strict_mode_accept_keep_core() {
+		let kind = ScriptKind::Function;
+		let path = Path::new("data/rezero/keep/tea.mcfunction");
+		for (lower, higher) in &[("Rem", "Ram"), ("Ram", "Rem")] {
+			let lower = script_of("tea.mcfunction", kind, lower, "say tea");
+			let higher = script_of("tea.mcfunction", kind, higher, "say broom");
+			let context = strict_merge(lower, higher, path, Some("Rem"));
+			assert!(context.errors.is_empty());
+
+			let conflict = context.report.conflicts().next().unwrap();
+			assert_eq!(conflict.winner.as_deref(), Some("Rem"));
+			assert_eq!(conflict.strategy, Strategy::Override);
+		}
 	}
 }
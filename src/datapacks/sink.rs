@@ -0,0 +1,245 @@
+use super::CompiledResult;
+use crate::utils::os_str_to_string;
+use std::fs::{self, Permissions};
+use std::io::{self, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use tempfile::{Builder, NamedTempFile, TempDir};
+use zip::write::FileOptions;
+use zip::ZipWriter;
+
+/// Destination that compiled data tree is written into
+///
+/// Paths are relative to datapack root.
+pub trait OutputSink {
+	fn add_directory(&mut self, path: &Path) -> CompiledResult<()>;
+	fn add_file(&mut self, path: &Path, data: &[u8]) -> CompiledResult<()>;
+	/// Flush everything that was written, nothing should be written after this
+	fn finish(&mut self) -> CompiledResult<()>;
+}
+
+/// Write merged datapack into a single zip file
+///
+/// The zip is written into a temporary file next to `path` which only replace `path` once it is finished,
+/// so a failed compile never leaves a broken zip in place of the previous one.
+pub struct ZipSink {
+	path: PathBuf,
+	zip: ZipWriter<NamedTempFile>,
+	options: FileOptions,
+	/// Total size of every files before compression
+	uncompressed: u64,
+	/// Size of the zip file, only known once it is finished
+	compressed: u64,
+}
+
+impl ZipSink {
+	pub fn create(path: impl Into<PathBuf>, options: FileOptions) -> io::Result<ZipSink> {
+		let path = path.into();
+		let staging = Builder::new()
+			.prefix(".centrosome-")
+			.suffix(".zip")
+			.tempfile_in(staging_parent(&path))?;
+		Ok(ZipSink {
+			path,
+			zip: ZipWriter::new(staging),
+			options,
+			uncompressed: 0,
+			compressed: 0,
+		})
+	}
+
+	/// Size of every files written into this zip and size of the finished zip file
+	pub fn sizes(&self) -> (u64, u64) {
+		(self.uncompressed, self.compressed)
+	}
+}
+
+impl OutputSink for ZipSink {
+	fn add_directory(&mut self, path: &Path) -> CompiledResult<()> {
+		self.zip.add_directory(zip_name(path), self.options)?;
+		Ok(())
+	}
+
+	fn add_file(&mut self, path: &Path, data: &[u8]) -> CompiledResult<()> {
+		self.zip.start_file(zip_name(path), self.options)?;
+		self.zip.write_all(data)?;
+		self.uncompressed += data.len() as u64;
+		Ok(())
+	}
+
+	/// Move the written zip into place of the previous one
+	fn finish(&mut self) -> CompiledResult<()> {
+		let staging = self.zip.finish()?;
+		let metadata = staging.as_file().metadata()?;
+		self.compressed = metadata.len();
+		staging
+			.as_file()
+			.set_permissions(output_permissions(&self.path, metadata.permissions()))?;
+		staging.persist(&self.path).map_err(io::Error::from)?;
+		Ok(())
+	}
+}
+
+/// Temporary files are only accessible by their owner, keep permissions of the previous zip or use the usual ones instead
+fn output_permissions(path: &Path, permissions: Permissions) -> Permissions {
+	match fs::metadata(path) {
+		Ok(metadata) => metadata.permissions(),
+		Err(_) => default_permissions(permissions),
+	}
+}
+
+#[cfg(unix)]
+fn default_permissions(mut permissions: Permissions) -> Permissions {
+	use std::os::unix::fs::PermissionsExt;
+	permissions.set_mode(0o644);
+	permissions
+}
+
+#[cfg(not(unix))]
+fn default_permissions(permissions: Permissions) -> Permissions {
+	permissions
+}
+
+/// Folder that output at `path` is staged in, so it can be renamed into place
+fn staging_parent(path: &Path) -> PathBuf {
+	match path.parent() {
+		Some(parent) if parent != Path::new("") => parent.to_path_buf(),
+		_ => PathBuf::from("."),
+	}
+}
+
+/// Name of zip entry at `path`, zip always use `/` as separator regardless of the platform
+fn zip_name(path: &Path) -> String {
+	path.iter()
+		.map(os_str_to_string)
+		.collect::<Vec<String>>()
+		.join("/")
+}
+
+/// Write merged datapack as a plain directory, such as straight into `world/datapacks/<name>`
+///
+/// Files are written into a temporary directory next to `root` which only replace `root` once everything was written,
+/// so a failed compile never leaves a half-written or missing datapack behind.
+pub struct DirectorySink {
+	root: PathBuf,
+	staging: TempDir,
+}
+
+impl DirectorySink {
+	/// Previous output at `root` is replaced, but only if it looks like a datapack so other folders are never deleted
+	pub fn create(root: impl Into<PathBuf>) -> io::Result<DirectorySink> {
+		let root = root.into();
+		if root.exists() && !root.join("pack.mcmeta").is_file() {
+			return Err(io::Error::new(
+				ErrorKind::AlreadyExists,
+				format!(
+					"'{}' already exists and is not a datapack, refusing to replace it",
+					root.display()
+				),
+			));
+		}
+
+		let parent = staging_parent(&root);
+		fs::create_dir_all(&parent)?;
+		let staging = Builder::new().prefix(".centrosome-").tempdir_in(&parent)?;
+		Ok(DirectorySink { root, staging })
+	}
+}
+
+impl OutputSink for DirectorySink {
+	fn add_directory(&mut self, path: &Path) -> CompiledResult<()> {
+		fs::create_dir_all(self.staging.path().join(path))?;
+		Ok(())
+	}
+
+	fn add_file(&mut self, path: &Path, data: &[u8]) -> CompiledResult<()> {
+		let path = self.staging.path().join(path);
+		if let Some(parent) = path.parent() {
+			fs::create_dir_all(parent)?;
+		}
+		fs::write(path, data)?;
+		Ok(())
+	}
+
+	/// Move previous output out of the way, then move the written directory into its place
+	fn finish(&mut self) -> CompiledResult<()> {
+		let previous = Builder::new()
+			.prefix(".centrosome-previous-")
+			.tempdir_in(staging_parent(&self.root))?;
+		let backup = previous.path().join("datapack");
+		let replaced = self.root.exists();
+		if replaced {
+			fs::rename(&self.root, &backup)?;
+		}
+
+		if let Err(error) = fs::rename(self.staging.path(), &self.root) {
+			if replaced {
+				fs::rename(&backup, &self.root)?;
+			}
+			return Err(error.into());
+		}
+
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn directory_sink_refuse_to_replace_other_folders() {
+		let directory = tempfile::tempdir().unwrap();
+		let output = directory.path().join("homework");
+		fs::create_dir_all(&output).unwrap();
+		fs::write(output.join("essay.txt"), "Do not delete me").unwrap();
+
+		assert!(DirectorySink::create(&output).is_err());
+		assert!(output.join("essay.txt").is_file());
+
+		fs::write(output.join("pack.mcmeta"), "{}").unwrap();
+		let mut sink = DirectorySink::create(&output).unwrap();
+		sink.add_file(Path::new("data/rem/functions/tea.mcfunction"), b"say tea")
+			.unwrap();
+		sink.finish().unwrap();
+		assert!(!output.join("essay.txt").exists());
+		assert_eq!(
+			fs::read(output.join("data/rem/functions/tea.mcfunction")).unwrap(),
+			b"say tea"
+		);
+	}
+
+	#[test]
+	fn zip_sink_keep_previous_zip_until_finished() {
+		let directory = tempfile::tempdir().unwrap();
+		let output = directory.path().join("ram.zip");
+		fs::write(&output, "previous").unwrap();
+
+		let mut sink = ZipSink::create(&output, FileOptions::default()).unwrap();
+		sink.add_file(Path::new("pack.mcmeta"), b"next").unwrap();
+		drop(sink);
+		assert_eq!(fs::read(&output).unwrap(), b"previous");
+		assert_eq!(fs::read_dir(directory.path()).unwrap().count(), 1);
+
+		let mut sink = ZipSink::create(&output, FileOptions::default()).unwrap();
+		sink.add_file(Path::new("pack.mcmeta"), b"next").unwrap();
+		sink.finish().unwrap();
+		let mut zip = zip::ZipArchive::new(fs::File::open(&output).unwrap()).unwrap();
+		assert_eq!(zip.by_name("pack.mcmeta").unwrap().size(), 4);
+		assert_eq!(fs::read_dir(directory.path()).unwrap().count(), 1);
+	}
+
+	#[test]
+	fn directory_sink_keep_previous_output_until_finished() {
+		let directory = tempfile::tempdir().unwrap();
+		let output = directory.path().join("ram");
+		fs::create_dir_all(&output).unwrap();
+		fs::write(output.join("pack.mcmeta"), "previous").unwrap();
+
+		let mut sink = DirectorySink::create(&output).unwrap();
+		sink.add_file(Path::new("pack.mcmeta"), b"next").unwrap();
+		drop(sink);
+
+		assert_eq!(fs::read(output.join("pack.mcmeta")).unwrap(), b"previous");
+		assert_eq!(fs::read_dir(directory.path()).unwrap().count(), 1);
+	}
+}
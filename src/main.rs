@@ -1,119 +1,365 @@
 #[macro_use]
 extern crate clap;
 
-use clap::App;
+use clap::{App, ArgMatches};
 use colored::*;
-use indicatif::{ProgressBar, ProgressStyle};
+use indicatif::{HumanBytes, ProgressBar, ProgressStyle};
 use rayon::prelude::*;
 
 use dialoguer::theme::ColorfulTheme;
-use dialoguer::{Input, Select};
+use dialoguer::{Input, OrderList};
 
 use std::io;
 use std::path::{Path, PathBuf};
+use std::process;
 
+mod commands;
 mod datapack_loader;
 mod datapacks;
+mod manifest;
 mod utils;
 
-use datapack_loader::DatapackLoader;
-use datapacks::Datapack;
+use datapack_loader::{DatapackLoader, PackType};
+use datapacks::{
+	function_names, Datapack, DirectorySink, LoadedFunctions, MergeContext, MergeRules, TreeError,
+	ZipSink,
+};
+use manifest::{Compression, Manifest, ManifestError, OutputFormat, MANIFEST_NAME};
 use utils::{
-	get_compression_method, get_datapacks, os_str_to_string, DatapackIterator, MergeError,
+	discover_datapacks, get_zip_timestamp, prioritize_core, read_order_file, validate_order,
+	Discovery, MergeError,
 };
 
+const DEFAULT_DATAPACK_NAME: &str = "merged_datapack";
+const DEFAULT_RESOURCE_PACK_NAME: &str = "merged_resource_pack";
+
 fn main() {
 	let yaml = load_yaml!("../resource/cli.yml");
 	let matches = App::from_yaml(yaml).get_matches();
 
+	let result = match matches.subcommand() {
+		("merge", Some(matches)) => run(matches, merge),
+		("init", Some(matches)) => run(matches, commands::init),
+		("inspect", Some(matches)) => commands::inspect(matches),
+		("diff", Some(matches)) => commands::diff(matches),
+		("validate", Some(matches)) => commands::validate(matches),
+		("extract", Some(matches)) => commands::extract(matches),
+		_ => unreachable!("Unknown subcommand"),
+	};
+
+	if let Err(error) = result {
+		eprintln!("{}", error);
+		process::exit(1);
+	}
+}
+
+type Command = fn(&Path, &ArgMatches) -> Result<(), MergeError>;
+
+/// Make sure that `directory` argument is a valid directory before running `command`
+fn run(matches: &ArgMatches, command: Command) -> Result<(), MergeError> {
 	let directory = matches
 		.value_of("directory")
 		.expect("Invalid directory name");
 	let directory = Path::new(directory);
 
-	if directory.exists() {
-		if directory.is_dir() {
-			if let Err(error) = merge(&directory) {
-				eprintln!("{}", error);
-			}
-		} else {
-			eprintln!(
-				"'{}' is not a directory!",
-				directory.display().to_string().cyan()
-			);
-		}
+	if !directory.exists() {
+		Err(MergeError::NotFound(directory.to_path_buf()))
+	} else if !directory.is_dir() {
+		Err(MergeError::NotDirectory(directory.to_path_buf()))
 	} else {
-		eprintln!(
-			"'{}' {}",
-			directory.display().to_string().cyan(),
-			"does not exists.".red()
-		);
+		command(directory, matches)
 	}
 }
 
-fn merge(directory: &Path) -> Result<(), MergeError> {
-	let datapack_entries = get_datapacks(directory)?;
-	let (selection_items, datapack_entries) = get_selection_items(datapack_entries);
+fn merge(directory: &Path, matches: &ArgMatches) -> Result<(), MergeError> {
+	let pack_type = get_pack_type(matches);
+	let non_interactive = matches.is_present("yes");
+	let dry_run = matches.is_present("dry-run");
+	let manifest = load_manifest(directory, matches)?.unwrap_or_default();
 
-	let selection = match ask_core_datapack(&selection_items)? {
-		Some(x) => x,
-		None => return Err(MergeError::Cancel),
+	let datapack_name = match matches.value_of("name").or(manifest.name.as_deref()) {
+		Some(name) => name.to_string(),
+		None if non_interactive || dry_run => default_name(pack_type).to_string(),
+		None => ask_merged_datapack_name(pack_type)?,
+	};
+	let format = matches
+		.value_of("output-format")
+		.and_then(OutputFormat::from_name)
+		.unwrap_or(manifest.format);
+	let output_path = match matches.value_of("output") {
+		Some(path) => PathBuf::from(path),
+		None => get_output_path(directory, &datapack_name, format),
 	};
-	let datapack_name = ask_merged_datapack_name()?;
 
-	let selection = &selection_items[selection];
+	let mut discovery = discover_datapacks(directory, matches.is_present("recursive"), pack_type)?;
+	// Merged datapack of a previous run is not one of the datapacks to merge
+	let output = absolute_path(&output_path);
+	let previous_output = discovery
+		.datapacks
+		.iter()
+		.position(|loader| absolute_path(&loader.path) == output)
+		.map(|index| discovery.datapacks.remove(index).name);
+	if matches.is_present("verbose") {
+		discovery.print_rejected();
+	}
+	let Discovery {
+		datapacks: datapack_entries,
+		rejected,
+	} = discovery;
+	let (selection_items, datapack_entries) = get_selection_items(datapack_entries);
+
+	let order =
+		get_merge_order(&selection_items, matches, &manifest).map_err(|error| match error {
+			MergeError::UnknownDatapack(name) if previous_output.as_ref() == Some(&name) => {
+				MergeError::OutputIsInput(output_path.clone())
+			}
+			error => error,
+		})?;
+	if !dry_run {
+		check_output_path(&output_path, &order, &datapack_entries)?;
+	}
+
+	let mut context = MergeContext::default();
+	context.chain_functions = matches.is_present("chain-functions") || manifest.chain_functions;
+	context.rules = get_merge_rules(matches, &manifest)?;
+	// Without `--core`, `keep-core` rules keep the highest priority datapack's file
+	context.core = matches
+		.value_of("core")
+		.map(String::from)
+		.or_else(|| order.last().cloned());
+	context.strict = matches.is_present("strict") || manifest.strict;
+	context.pack_type = pack_type;
+	context.function_names = function_names(&order);
+	for (path, rejection) in &rejected {
+		context
+			.report
+			.reject(path, rejection.name(), rejection.to_string());
+	}
 
-	let (core_datapack, core_size) = get_core_datapack(&selection, &datapack_entries, |_| {})?;
-	let (datapacks, sizes): (Vec<Datapack>, Vec<u64>) =
-		get_other_datapack(&selection, &datapack_entries, |_| {});
-	let total_size = core_size + sizes.iter().sum::<u64>();
+	let wrap_load = matches.is_present("wrap-load") || manifest.wrap_load;
+	let (datapacks, total_size, problems) =
+		get_ordered_datapacks(&order, &datapack_entries, wrap_load, |_| {})?;
+	problems
+		.into_iter()
+		.for_each(|problem| context.warn(problem));
 
-	let temp_dir = tempfile::tempdir()?;
-	let mut output_datapack = Datapack::from(temp_dir.path());
+	let mut output_datapack = Datapack::empty(datapack_name.as_str());
 
+	// Datapacks are sorted from lowest to highest priority so later datapack always win the conflict
 	for datapack in datapacks {
-		if &datapack.name != selection {
-			output_datapack = output_datapack.merge(datapack, |_| {})?;
+		output_datapack = output_datapack.merge(datapack, &mut context, |_| {})?;
+	}
+
+	if !context.errors.is_empty() {
+		for error in &context.errors {
+			eprintln!("{} {}", "error:".red().bold(), error);
 		}
+		return Err(MergeError::Unresolved(context.errors.len()));
 	}
 
-	output_datapack = output_datapack.merge(core_datapack, |_| {})?;
+	let (compression, level) = get_compression(matches, &manifest)?;
 
-	let output_path = get_output_path(&directory, &datapack_name);
+	if dry_run {
+		print_dry_run(&output_datapack, &output_path, total_size);
+	} else {
+		let compiling_bar = prepare_compiling_progress_bar(total_size);
+		let options = prepare_zip_options(compression, level);
 
-	let compiling_bar = prepare_compiling_progress_bar(total_size);
-	let options = prepare_zip_options();
+		let event = |delta| compiling_bar.inc(delta);
+		let sizes = match format {
+			OutputFormat::Zip => {
+				let mut sink = ZipSink::create(&output_path, options)?;
+				output_datapack.compile(&mut sink, event)?;
+				Some(sink.sizes())
+			}
+			OutputFormat::Dir => {
+				output_datapack.compile(&mut DirectorySink::create(&output_path)?, event)?;
+				None
+			}
+		};
 
-	output_datapack.compile(&output_path, &options, |delta| compiling_bar.inc(delta))?;
+		compiling_bar.finish();
 
-	compiling_bar.finish();
+		println!(
+			"Compiled datapack to: '{}'",
+			output_path.display().to_string().cyan()
+		);
+		// Zip size includes headers and directory entries, so tiny datapacks can be bigger than their files
+		if let Some((uncompressed, zipped)) = sizes {
+			let ratio = zipped as f64 * 100.0 / uncompressed.max(1) as f64;
+			println!(
+				"Zip size: {} ({:.0}% of {} input, {})",
+				HumanBytes(zipped),
+				ratio,
+				HumanBytes(uncompressed),
+				compression.name()
+			);
+		}
+	}
 
-	println!(
-		"Compiled datapack to: '{}'",
-		output_path.display().to_string().cyan()
-	);
+	let report = context.report;
+	if !report.is_empty() {
+		print!("{}", report);
+	}
+	if let Some(report_path) = matches.value_of("report") {
+		report.save(Path::new(report_path))?;
+		println!(
+			"Saved conflict report to: '{}'",
+			report_path.to_string().cyan()
+		);
+	}
 
 	Ok(())
 }
 
-fn ask_core_datapack(selection_items: &[String]) -> io::Result<Option<usize>> {
-	Select::with_theme(&ColorfulTheme::default())
-		.with_prompt("Please choose core datapack")
-		.default(0)
-		.items(&selection_items)
+/// `--resource-pack` merges packs with `assets` folder instead of `data` folder
+fn get_pack_type(matches: &ArgMatches) -> PackType {
+	if matches.is_present("resource-pack") {
+		PackType::Resource
+	} else {
+		PackType::Data
+	}
+}
+
+fn default_name(pack_type: PackType) -> &'static str {
+	match pack_type {
+		PackType::Data => DEFAULT_DATAPACK_NAME,
+		PackType::Resource => DEFAULT_RESOURCE_PACK_NAME,
+	}
+}
+
+/// Rules from `--rule` are placed before rules from manifest so they take precedence
+fn get_merge_rules(matches: &ArgMatches, manifest: &Manifest) -> Result<MergeRules, MergeError> {
+	let mut rules = MergeRules::default();
+	for value in matches.values_of("rule").into_iter().flatten() {
+		let invalid = || MergeError::InvalidRule(value.to_string());
+		let (pattern, rule) = MergeRules::parse(value).ok_or_else(invalid)?;
+		rules.push(pattern, rule).map_err(|_| invalid())?;
+	}
+	for entry in &manifest.rules {
+		rules
+			.push(&entry.path, entry.rule)
+			.map_err(|error| ManifestError::Pattern(entry.path.clone(), error))?;
+	}
+	Ok(rules)
+}
+
+/// Compression method and level of zipped output from `--compression` and `--level`, the manifest's compression is used otherwise
+fn get_compression(
+	matches: &ArgMatches,
+	manifest: &Manifest,
+) -> Result<(Compression, Option<i32>), MergeError> {
+	let compression = matches
+		.value_of("compression")
+		.and_then(Compression::from_name)
+		.or(manifest.compression)
+		.unwrap_or_default();
+
+	let level = match matches.value_of("level") {
+		Some(value) => {
+			let invalid = || MergeError::InvalidLevel(value.to_string(), compression);
+			let level: i32 = value.parse().map_err(|_| invalid())?;
+			match compression.levels() {
+				Some(levels) if levels.contains(&level) => Some(level),
+				_ => return Err(invalid()),
+			}
+		}
+		None => None,
+	};
+
+	Ok((compression, level))
+}
+
+/// Load manifest from `--manifest` argument or from `centrosome.toml` inside `directory` if there is one
+fn load_manifest(directory: &Path, matches: &ArgMatches) -> Result<Option<Manifest>, MergeError> {
+	let path = match matches.value_of("manifest") {
+		Some(path) => PathBuf::from(path),
+		None => directory.join(MANIFEST_NAME),
+	};
+
+	if matches.is_present("manifest") || path.is_file() {
+		Ok(Some(Manifest::load(&path)?))
+	} else {
+		Ok(None)
+	}
+}
+
+/// Return name of every datapacks that will be merged, sorted from lowest to highest priority
+fn get_merge_order(
+	selection_items: &[String],
+	matches: &ArgMatches,
+	manifest: &Manifest,
+) -> Result<Vec<String>, MergeError> {
+	let core = matches.value_of("core");
+
+	let order = if let Some(packs) = matches.values_of("pack") {
+		packs.map(String::from).collect()
+	} else if let Some(order_file) = matches.value_of("order") {
+		read_order_file(Path::new(order_file))?
+	} else if !manifest.packs.is_empty() {
+		manifest.resolve_packs(selection_items)?
+	} else if let Some(core) = core {
+		// Without an explicit order, the core datapack has the highest priority
+		let mut order = selection_items.to_vec();
+		order.sort();
+		prioritize_core(order, core)
+	} else if matches.is_present("yes") {
+		return Err(MergeError::MissingArgument("--core' or '--pack"));
+	} else {
+		ask_datapack_order(selection_items)?
+	};
+
+	validate_order(&order, selection_items)?;
+	// With an explicit order, `--core` only choose the datapack kept by `keep-core` rules
+	match core {
+		Some(core) if !order.iter().any(|name| name == core) => {
+			Err(MergeError::UnknownDatapack(core.to_string()))
+		}
+		_ => Ok(order),
+	}
+}
+
+fn ask_datapack_order(selection_items: &[String]) -> io::Result<Vec<String>> {
+	let mut items = selection_items.to_vec();
+	items.sort();
+
+	let order = OrderList::with_theme(&ColorfulTheme::default())
+		.with_prompt("Order datapacks from lowest to highest priority")
+		.items(&items)
 		.paged(true)
-		.interact_opt()
+		.interact()?;
+
+	Ok(order.into_iter().map(|index| items[index].clone()).collect())
 }
 
-fn ask_merged_datapack_name() -> io::Result<String> {
+fn ask_merged_datapack_name(pack_type: PackType) -> io::Result<String> {
 	Input::with_theme(&ColorfulTheme::default())
 		.with_prompt("Merged datapack name")
-		.default("merged_datapack".to_string())
+		.default(default_name(pack_type).to_string())
 		.allow_empty(false)
 		.show_default(true)
 		.interact()
 }
 
+/// Print what would have been written by merging process without writing anything
+fn print_dry_run(datapack: &Datapack, output_path: &Path, input_size: u64) {
+	let files = datapack.flatten();
+	let size: u64 = files.values().map(|data| data.len() as u64).sum();
+
+	println!("{}", "Merged datapack".bold());
+	commands::print_tree(&files);
+	println!(
+		"{} files, {} (input datapacks: {})",
+		files.len(),
+		HumanBytes(size),
+		HumanBytes(input_size)
+	);
+	println!(
+		"Dry run, '{}' was not written",
+		output_path.display().to_string().cyan()
+	);
+}
+
 fn prepare_compiling_progress_bar(size: u64) -> ProgressBar {
 	let template = format!(
 		"[{{elapsed}}] {} [{{wide_bar:.white}}] {{bytes}}/{{total_bytes}}",
@@ -124,65 +370,112 @@ fn prepare_compiling_progress_bar(size: u64) -> ProgressBar {
 }
 
 use zip::write::FileOptions;
+/// Every entries share the same timestamp and permissions so the output only depends on the datapacks being merged
 #[cfg(not(windows))]
-fn prepare_zip_options() -> FileOptions {
+fn prepare_zip_options(compression: Compression, level: Option<i32>) -> FileOptions {
 	FileOptions::default()
-		.compression_method(get_compression_method())
+		.compression_method(compression.into())
+		.compression_level(level)
+		.last_modified_time(get_zip_timestamp())
 		.unix_permissions(0o775)
 }
 
 /// Window doesn't have concept of "unix permissions", if we try to create a file with unix permissions it will result in Inaccessible file permission.
 #[cfg(windows)]
-fn prepare_zip_options() -> FileOptions {
+fn prepare_zip_options(compression: Compression, level: Option<i32>) -> FileOptions {
 	FileOptions::default()
-		.compression_method(get_compression_method())
+		.compression_method(compression.into())
+		.compression_level(level)
+		.last_modified_time(get_zip_timestamp())
+}
+
+/// Make sure that writing into `output` cannot replace or write inside any of the datapacks in `order`
+fn check_output_path(
+	output: &Path,
+	order: &[String],
+	datapacks: &[DatapackLoader],
+) -> Result<(), MergeError> {
+	let output = absolute_path(output);
+	let merged = datapacks
+		.iter()
+		.filter(|datapack| order.contains(&datapack.name));
+	for datapack in merged {
+		let input = absolute_path(&datapack.path);
+		if output.starts_with(&input) || input.starts_with(&output) {
+			return Err(MergeError::OutputIsInput(datapack.path.clone()));
+		}
+	}
+	Ok(())
+}
+
+/// Canonical form of `path` even if it doesn't exist yet, so different spellings of the same path compare equal
+fn absolute_path(path: &Path) -> PathBuf {
+	if let Ok(path) = path.canonicalize() {
+		return path;
+	}
+	match (path.parent(), path.file_name()) {
+		(Some(parent), Some(name)) if parent != Path::new("") => absolute_path(parent).join(name),
+		(_, Some(name)) => absolute_path(Path::new(".")).join(name),
+		_ => path.to_path_buf(),
+	}
 }
 
-fn get_selection_items(datapack_entries: DatapackIterator) -> (Vec<String>, Vec<DatapackLoader>) {
+fn get_selection_items(
+	datapack_entries: Vec<DatapackLoader>,
+) -> (Vec<String>, Vec<DatapackLoader>) {
 	datapack_entries
-		.map(|entry| -> (String, DatapackLoader) {
-			let name = os_str_to_string(&entry.file_name());
-			let loader = DatapackLoader::new(entry.path()).unwrap();
-			(name, loader)
-		})
+		.into_iter()
+		.map(|loader| (loader.name.clone(), loader))
 		.unzip()
 }
 
-fn get_core_datapack(
-	name: &str,
+/// Generate every datapacks inside `order` while keeping their order intact
+///
+/// Returns those datapacks along with their total size and every files that cannot be read
+fn get_ordered_datapacks(
+	order: &[String],
 	datapacks: &[DatapackLoader],
+	wrap_load: bool,
 	event: impl Fn(u64) + Sync + Send + Copy,
-) -> Result<(Datapack, u64), MergeError> {
-	datapacks
+) -> Result<(Vec<Datapack>, u64, Vec<TreeError>), MergeError> {
+	let datapacks: Vec<(Datapack, u64, Vec<TreeError>)> = order
 		.par_iter()
-		.find_any(|datapack| datapack.name == name)
-		.cloned()
-		.map(|loader| {
-			let datapack = Datapack::generate(&loader.path, event).unwrap();
-			loader.cleanup();
-			datapack
-		})
-		.ok_or(MergeError::Other("Cannot find core datapack"))
-}
+		.map(
+			|name| -> Result<(Datapack, u64, Vec<TreeError>), MergeError> {
+				let loader = datapacks
+					.iter()
+					.find(|loader| &loader.name == name)
+					.ok_or_else(|| MergeError::UnknownDatapack(name.clone()))?;
+				let mut problems = Vec::new();
+				let (datapack, size) = Datapack::generate(&loader.path, &mut problems, event)?;
+				let datapack = datapack.with_origin(name.as_str());
+				Ok((datapack, size, problems))
+			},
+		)
+		.collect::<Result<_, _>>()?;
 
-fn get_other_datapack(
-	name: &str,
-	datapacks: &[DatapackLoader],
-	event: impl Fn(u64) + Sync + Send + Copy,
-) -> (Vec<Datapack>, Vec<u64>) {
-	datapacks
-		.par_iter()
-		.filter(|loader| loader.name != name)
-		.map(|loader| {
-			let datapack = Datapack::generate(&loader.path, event).unwrap();
-			loader.cleanup();
+	// Wrappers are created in priority order so functions shared by several datapacks are only loaded once
+	let names = function_names(order);
+	let mut loaded = LoadedFunctions::new();
+	let mut result = (Vec::new(), 0, Vec::new());
+	for (name, (datapack, size, problems)) in order.iter().zip(datapacks) {
+		let datapack = if wrap_load {
+			datapack.wrap_load(&names[name], &mut loaded)?
+		} else {
 			datapack
-		})
-		.unzip()
+		};
+		result.0.push(datapack);
+		result.1 += size;
+		result.2.extend(problems);
+	}
+	Ok(result)
 }
 
-fn get_output_path(directory: impl Into<PathBuf>, name: &str) -> PathBuf {
+fn get_output_path(directory: impl Into<PathBuf>, name: &str, format: OutputFormat) -> PathBuf {
 	let directory = directory.into();
-	let output_file = PathBuf::from(format!("{}.zip", name));
+	let output_file = match format.extension() {
+		Some(extension) => PathBuf::from(format!("{}.{}", name, extension)),
+		None => PathBuf::from(name),
+	};
 	directory.join(output_file)
 }
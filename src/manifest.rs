@@ -0,0 +1,327 @@
+use crate::datapacks::MergeRule;
+use glob::Pattern;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use zip::CompressionMethod;
+
+/// Default name of the manifest file, looked up inside datapacks directory
+pub const MANIFEST_NAME: &str = "centrosome.toml";
+
+/// Representing `centrosome.toml` that describe how datapacks should be merged
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Manifest {
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub name: Option<String>,
+	pub format: OutputFormat,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub compression: Option<Compression>,
+	/// Datapacks or glob patterns of datapacks, sorted from lowest to highest priority
+	pub packs: Vec<String>,
+	/// Move `minecraft:load` values of each datapack into its own wrapper function
+	pub wrap_load: bool,
+	/// Keep every conflicting functions and call them in priority order
+	pub chain_functions: bool,
+	/// Fail the merge on any conflict that was resolved by discarding a file
+	pub strict: bool,
+	/// Per-path merge rules, the first rule matching a resource is used
+	#[serde(skip_serializing_if = "Vec::is_empty")]
+	pub rules: Vec<RuleEntry>,
+}
+
+/// Merge rule for every resources matching `path` glob pattern
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct RuleEntry {
+	pub path: String,
+	pub rule: MergeRule,
+}
+
+impl Manifest {
+	pub fn load(path: &Path) -> Result<Manifest, ManifestError> {
+		let content =
+			fs::read_to_string(path).map_err(|error| ManifestError::Io(path.into(), error))?;
+		toml::from_str(&content).map_err(|error| ManifestError::Decode(path.into(), error))
+	}
+
+	/// Write this manifest to `path`, refuse to override existing file unless `force` is true
+	pub fn save(&self, path: &Path, force: bool) -> Result<(), ManifestError> {
+		if path.exists() && !force {
+			return Err(ManifestError::AlreadyExists(path.into()));
+		}
+
+		let content = toml::to_string_pretty(self).map_err(ManifestError::Encode)?;
+		let content = format!("{}{}", MANIFEST_HEADER, content);
+		fs::write(path, content).map_err(|error| ManifestError::Io(path.into(), error))
+	}
+
+	/// Create a starter manifest that merge every `datapacks` in alphabetical order into `name`
+	pub fn starter(datapacks: &[String], name: &str) -> Manifest {
+		let mut packs = datapacks.to_vec();
+		packs.sort();
+		Manifest {
+			name: Some(name.to_string()),
+			format: OutputFormat::default(),
+			compression: Some(Compression::default()),
+			packs,
+			wrap_load: false,
+			chain_functions: false,
+			strict: false,
+			rules: Vec::new(),
+		}
+	}
+
+	/// Expand glob patterns inside `packs` into datapack names from `available`
+	///
+	/// Datapack that match more than one pattern will only keep its last position so `["*", "core"]` will put `core` at the highest priority.
+	pub fn resolve_packs(&self, available: &[String]) -> Result<Vec<String>, ManifestError> {
+		let mut sorted = available.to_vec();
+		sorted.sort();
+
+		let mut order: Vec<String> = Vec::new();
+		for value in &self.packs {
+			let matched: Vec<&String> = if sorted.contains(value) {
+				vec![value]
+			} else {
+				let pattern = Pattern::new(value)
+					.map_err(|error| ManifestError::Pattern(value.clone(), error))?;
+				sorted.iter().filter(|name| pattern.matches(name)).collect()
+			};
+
+			if matched.is_empty() {
+				return Err(ManifestError::Unmatched(value.clone()));
+			}
+
+			for name in matched {
+				order.retain(|other| other != name);
+				order.push(name.clone());
+			}
+		}
+
+		Ok(order)
+	}
+}
+
+const MANIFEST_HEADER: &str = "# Centrosome merge manifest
+# `packs` are sorted from lowest to highest priority and may contain glob patterns
+
+";
+
+/// Output format of the merged datapack
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+	#[default]
+	Zip,
+	/// Plain directory, useful to write straight into `world/datapacks` during development
+	Dir,
+}
+
+impl OutputFormat {
+	pub fn from_name(name: &str) -> Option<OutputFormat> {
+		match name {
+			"zip" => Some(OutputFormat::Zip),
+			"dir" => Some(OutputFormat::Dir),
+			_ => None,
+		}
+	}
+
+	/// File extension of the output, directories don't have one
+	pub fn extension(self) -> Option<&'static str> {
+		match self {
+			OutputFormat::Zip => Some("zip"),
+			OutputFormat::Dir => None,
+		}
+	}
+}
+
+/// Compression method of the merged datapack
+///
+/// Minecraft can only read `stored` and `deflate` zips, `bzip2` and `zstd` are meant for archiving.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+	Stored,
+	#[default]
+	Deflate,
+	Bzip2,
+	Zstd,
+}
+
+impl Compression {
+	pub fn from_name(name: &str) -> Option<Compression> {
+		match name {
+			"stored" => Some(Compression::Stored),
+			"deflate" => Some(Compression::Deflate),
+			"bzip2" => Some(Compression::Bzip2),
+			"zstd" => Some(Compression::Zstd),
+			_ => None,
+		}
+	}
+
+	pub fn name(self) -> &'static str {
+		match self {
+			Compression::Stored => "stored",
+			Compression::Deflate => "deflate",
+			Compression::Bzip2 => "bzip2",
+			Compression::Zstd => "zstd",
+		}
+	}
+
+	/// Compression levels accepted by this method, `stored` doesn't compress so it has none
+	pub fn levels(self) -> Option<RangeInclusive<i32>> {
+		match self {
+			Compression::Stored => None,
+			Compression::Deflate | Compression::Bzip2 => Some(1..=9),
+			Compression::Zstd => Some(1..=22),
+		}
+	}
+}
+
+impl From<Compression> for CompressionMethod {
+	fn from(compression: Compression) -> CompressionMethod {
+		match compression {
+			Compression::Stored => CompressionMethod::Stored,
+			Compression::Deflate => CompressionMethod::Deflated,
+			Compression::Bzip2 => CompressionMethod::Bzip2,
+			Compression::Zstd => CompressionMethod::Zstd,
+		}
+	}
+}
+
+use colored::*;
+use std::io;
+
+#[derive(Debug)]
+pub enum ManifestError {
+	Io(PathBuf, io::Error),
+	Decode(PathBuf, toml::de::Error),
+	Encode(toml::ser::Error),
+	Pattern(String, glob::PatternError),
+	Unmatched(String),
+	AlreadyExists(PathBuf),
+}
+
+use std::fmt;
+impl fmt::Display for ManifestError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			ManifestError::Io(path, error) => {
+				write!(f, "{} in '{}'", error, path.display().to_string().cyan())
+			}
+			ManifestError::Decode(path, error) => {
+				write!(f, "{} in '{}'", error, path.display().to_string().cyan())
+			}
+			ManifestError::Encode(error) => write!(f, "{}", error),
+			ManifestError::Pattern(pattern, error) => {
+				write!(f, "'{}' is not a valid pattern: {}", pattern.cyan(), error)
+			}
+			ManifestError::Unmatched(pattern) => {
+				write!(f, "'{}' does not match any datapack", pattern.cyan())
+			}
+			ManifestError::AlreadyExists(path) => write!(
+				f,
+				"'{}' already exists, use '--force' to override it",
+				path.display().to_string().cyan()
+			),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn names(values: &[&str]) -> Vec<String> {
+		values.iter().map(|value| value.to_string()).collect()
+	}
+
+	#[test]
+	fn decode_manifest() {
+		let data = r#"
+		name = "konosuba"
+		compression = "deflate"
+		packs = ["aqua", "darkness*", "megumin.zip"]
+		wrap-load = true
+		chain-functions = true
+
+		[[rules]]
+		path = "data/*/functions/**"
+		rule = "keep-core"
+		"#;
+
+		let value: Manifest = toml::from_str(data).unwrap();
+		let expect = Manifest {
+			name: Some(String::from("konosuba")),
+			format: OutputFormat::Zip,
+			compression: Some(Compression::Deflate),
+			packs: names(&["aqua", "darkness*", "megumin.zip"]),
+			wrap_load: true,
+			chain_functions: true,
+			strict: false,
+			rules: vec![RuleEntry {
+				path: String::from("data/*/functions/**"),
+				rule: MergeRule::KeepCore,
+			}],
+		};
+
+		assert_eq!(value, expect);
+	}
+
+	#[test]
+	fn compression_levels() {
+		assert_eq!(Compression::from_name("zstd"), Some(Compression::Zstd));
+		assert_eq!(Compression::from_name("lzma"), None);
+		assert_eq!(Compression::Stored.levels(), None);
+		assert_eq!(Compression::Deflate.levels(), Some(1..=9));
+		assert_eq!(Compression::Zstd.levels(), Some(1..=22));
+	}
+
+	#[test]
+	fn reject_unknown_fields() {
+		let data = r#"
+		packs = ["aqua"]
+		wrap_load = true
+		"#;
+		assert!(toml::from_str::<Manifest>(data).is_err());
+	}
+
+	#[test]
+	fn starter_manifest_round_trip() {
+		let manifest = Manifest::starter(&names(&["kazuma", "aqua"]), "merged_datapack");
+		let data = toml::to_string_pretty(&manifest).unwrap();
+		let value: Manifest = toml::from_str(&data).unwrap();
+
+		assert_eq!(value.packs, names(&["aqua", "kazuma"]));
+		assert_eq!(value, manifest);
+	}
+
+	#[test]
+	fn resolve_glob_packs() {
+		let manifest = Manifest {
+			packs: names(&["*", "aqua"]),
+			..Manifest::default()
+		};
+		let available = names(&["megumin.zip", "aqua", "darkness"]);
+
+		assert_eq!(
+			manifest.resolve_packs(&available).unwrap(),
+			names(&["darkness", "megumin.zip", "aqua"])
+		);
+	}
+
+	#[test]
+	fn resolve_unmatched_packs() {
+		let manifest = Manifest {
+			packs: names(&["wiz*"]),
+			..Manifest::default()
+		};
+
+		match manifest.resolve_packs(&names(&["aqua"])) {
+			Err(ManifestError::Unmatched(pattern)) => assert_eq!(pattern, "wiz*"),
+			other => panic!("Unexpected result: {:?}", other),
+		}
+	}
+}
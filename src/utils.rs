@@ -1,41 +1,193 @@
 use crate::datapacks::TreeError;
+use crate::manifest::{Compression, ManifestError};
+use std::fs;
 use std::io;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
-pub type DatapackIterator = Box<dyn Iterator<Item = DirEntry>>;
-pub type DatapacksResult = io::Result<DatapackIterator>;
-/// Return iterator over every valid datapacks inside `directory`
-pub fn get_datapacks(directory: &Path) -> DatapacksResult {
-	let result = directory
-		.read_dir()?
-		.filter_map(|entry| check_datapack(entry).ok());
-	Ok(Box::new(result))
+/// Entries inside a directory, split into datapacks and entries that were rejected
+#[derive(Debug, Default)]
+pub struct Discovery {
+	pub datapacks: Vec<DatapackLoader>,
+	pub rejected: Vec<(PathBuf, Rejection)>,
 }
 
-use super::DatapackLoader;
-use std::fs::DirEntry;
-use std::io::{Error, ErrorKind};
-/// Determine if `entry` is a datapack or not by checking for `/pack.mcmeta` and `/data` inside `entry`
-fn check_datapack(entry: io::Result<DirEntry>) -> io::Result<DirEntry> {
-	let entry = entry?;
-	let path = entry.path();
+impl Discovery {
+	/// Print every rejected entries along with the reason
+	pub fn print_rejected(&self) {
+		println!("{} ({}):", "Rejected".yellow().bold(), self.rejected.len());
+		for (path, rejection) in &self.rejected {
+			println!("  '{}' {}", path.display().to_string().cyan(), rejection);
+		}
+	}
 
-	let pack_mcmeta = DatapackLoader::peak(&path, "pack.mcmeta")?;
-	let data_folder = DatapackLoader::peak(&path, "data/")?;
+	/// Keep only the folder when a datapack exists both as `<name>` and `<name>.zip` next to each other
+	fn dedupe(&mut self) {
+		let folders: Vec<PathBuf> = self
+			.datapacks
+			.iter()
+			.filter(|loader| loader.path.is_dir())
+			.map(|loader| loader.path.clone())
+			.collect();
+		let (duplicates, datapacks): (Vec<DatapackLoader>, Vec<DatapackLoader>) =
+			self.datapacks.drain(..).partition(|loader| {
+				loader.path.is_file() && folders.contains(&loader.path.with_extension(""))
+			});
+
+		self.datapacks = datapacks;
+		for loader in duplicates {
+			let folder = loader.path.with_extension("");
+			self.rejected
+				.push((loader.path, Rejection::Duplicate(folder)));
+		}
+	}
+}
 
-	if is_datapack(pack_mcmeta, data_folder) {
-		Ok(entry)
+/// Check every entries inside `directory` and remember why each of them was rejected
+///
+/// With `recursive`, folders that are not datapacks are searched as well.
+/// Minecraft worlds and server roots are only searched inside their `datapacks` folder.
+pub fn discover_datapacks(
+	directory: &Path,
+	recursive: bool,
+	pack_type: PackType,
+) -> io::Result<Discovery> {
+	let mut discovery = Discovery::default();
+	if recursive {
+		discover_nested(directory, directory, pack_type, &mut discovery)?;
+		discovery.dedupe();
 	} else {
-		Err(Error::new(
-			ErrorKind::NotFound,
-			"This path is not a valid datapack",
-		))
+		discover_in(directory, directory, false, pack_type, &mut discovery)?;
+	}
+	Ok(discovery)
+}
+
+/// Search entries of `directory`, datapacks are named by their path relative to `root`
+fn discover_in(
+	root: &Path,
+	directory: &Path,
+	recursive: bool,
+	pack_type: PackType,
+	discovery: &mut Discovery,
+) -> io::Result<()> {
+	for entry in directory.read_dir()? {
+		let entry = entry?;
+		let path = entry.path();
+		if !is_candidate(&path) {
+			discovery.rejected.push((path, Rejection::NotPack));
+			continue;
+		}
+
+		match DatapackLoader::detect(&path, pack_type) {
+			Ok(()) => discovery
+				.datapacks
+				.push(DatapackLoader::relative(path, root)),
+			Err(Rejection::MissingPackMeta) if recursive && is_real_directory(&entry) => {
+				discover_nested(root, &path, pack_type, discovery)?
+			}
+			Err(rejection) => discovery.rejected.push((path, rejection)),
+		}
 	}
+	Ok(())
+}
+
+/// Minecraft only loads datapacks from folders and `.zip` files
+fn is_candidate(path: &Path) -> bool {
+	path.is_dir()
+		|| path
+			.extension()
+			.map(|extension| extension.eq_ignore_ascii_case("zip"))
+			.unwrap_or_default()
 }
 
-use std::fs::Metadata;
-fn is_datapack(pack_mcmeta: Metadata, data_folder: Metadata) -> bool {
-	pack_mcmeta.is_file() && data_folder.is_dir()
+/// Symbolic links are not followed so linking a parent folder cannot loop forever
+fn is_real_directory(entry: &DirEntry) -> bool {
+	entry
+		.file_type()
+		.map(|file_type| file_type.is_dir())
+		.unwrap_or_default()
+}
+
+/// Resource packs are not stored inside world folders so worlds are searched like any other folders
+fn discover_nested(
+	root: &Path,
+	directory: &Path,
+	pack_type: PackType,
+	discovery: &mut Discovery,
+) -> io::Result<()> {
+	let datapacks = match pack_type {
+		PackType::Data => world_datapacks(directory),
+		PackType::Resource => None,
+	};
+	match datapacks {
+		Some(datapacks) => discover_in(root, &datapacks, false, pack_type, discovery),
+		None => discover_in(root, directory, true, pack_type, discovery),
+	}
+}
+
+/// `datapacks` folder of `directory` if it is a Minecraft world or a server root
+///
+/// Server's world is read from `level-name` inside `server.properties`, default to `world`
+fn world_datapacks(directory: &Path) -> Option<PathBuf> {
+	let world = if directory.join("level.dat").is_file() {
+		directory.to_path_buf()
+	} else {
+		let properties = fs::read_to_string(directory.join("server.properties")).ok()?;
+		let level_name = properties
+			.lines()
+			.filter_map(|line| line.trim().strip_prefix("level-name="))
+			.next_back()
+			.unwrap_or("world");
+		directory.join(level_name)
+	};
+
+	let datapacks = world.join("datapacks");
+	if datapacks.is_dir() {
+		Some(datapacks)
+	} else {
+		None
+	}
+}
+
+use super::datapack_loader::{DatapackLoader, PackType, Rejection};
+use std::fs::DirEntry;
+use std::io::Error;
+
+/// Read datapack order from `path`
+///
+/// Each line contain one datapack name, sorted from lowest to highest priority.
+/// Empty lines and lines starting with `#` are ignored.
+pub fn read_order_file(path: &Path) -> io::Result<Vec<String>> {
+	let content = fs::read_to_string(path)?;
+	Ok(parse_order(&content))
+}
+
+fn parse_order(content: &str) -> Vec<String> {
+	content
+		.lines()
+		.map(str::trim)
+		.filter(|line| !line.is_empty() && !line.starts_with('#'))
+		.map(String::from)
+		.collect()
+}
+
+/// Move `core` to the end of `order` so that it will have the highest priority
+pub fn prioritize_core(mut order: Vec<String>, core: &str) -> Vec<String> {
+	order.retain(|name| name != core);
+	order.push(core.to_string());
+	order
+}
+
+/// Make sure that every datapack inside `order` exists and only appear once
+pub fn validate_order(order: &[String], available: &[String]) -> Result<(), MergeError> {
+	for (index, name) in order.iter().enumerate() {
+		if !available.contains(name) {
+			return Err(MergeError::UnknownDatapack(name.clone()));
+		}
+		if order[..index].contains(name) {
+			return Err(MergeError::DuplicateDatapack(name.clone()));
+		}
+	}
+	Ok(())
 }
 
 use std::ffi::OsString;
@@ -49,18 +201,97 @@ pub fn os_str_to_string(value: impl Into<OsString>) -> String {
 pub enum MergeError {
 	Io(Error),
 	Tree(TreeError),
-	Other(&'static str),
-	Cancel,
+	Manifest(ManifestError),
+	NotFound(PathBuf),
+	NotDirectory(PathBuf),
+	NotZip(PathBuf),
+	AlreadyExists(PathBuf),
+	/// Merged datapack would be written over one of the datapacks being merged
+	OutputIsInput(PathBuf),
+	InvalidDatapack(String, usize),
+	MissingArgument(&'static str),
+	UnknownDatapack(String),
+	DuplicateDatapack(String),
+	InvalidRule(String),
+	/// Compression level that the compression method doesn't accept
+	InvalidLevel(String, Compression),
+	Unresolved(usize),
 }
 
+use colored::*;
 use std::fmt;
 impl fmt::Display for MergeError {
 	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
 		match self {
 			MergeError::Io(error) => write!(f, "{}", error),
 			MergeError::Tree(error) => write!(f, "{}", error),
-			MergeError::Other(message) => write!(f, "{}", message),
-			MergeError::Cancel => write!(f, "Cancelled."),
+			MergeError::Manifest(error) => write!(f, "{}", error),
+			MergeError::NotFound(path) => write!(
+				f,
+				"'{}' {}",
+				path.display().to_string().cyan(),
+				"does not exists.".red()
+			),
+			MergeError::NotDirectory(path) => write!(
+				f,
+				"'{}' is not a directory!",
+				path.display().to_string().cyan()
+			),
+			MergeError::NotZip(path) => write!(
+				f,
+				"'{}' is not a zip file!",
+				path.display().to_string().cyan()
+			),
+			MergeError::AlreadyExists(path) => {
+				write!(f, "'{}' already exists.", path.display().to_string().cyan())
+			}
+			MergeError::OutputIsInput(path) => write!(
+				f,
+				"Output would overwrite datapack '{}', use '{}' or '{}' to write it somewhere else",
+				path.display().to_string().cyan(),
+				"--output".cyan(),
+				"--name".cyan()
+			),
+			MergeError::InvalidDatapack(name, count) => {
+				write!(f, "'{}' has {} problem(s).", name.cyan(), count)
+			}
+			MergeError::MissingArgument(argument) => write!(
+				f,
+				"'{}' is required when running without prompts",
+				argument.cyan()
+			),
+			MergeError::UnknownDatapack(name) => {
+				write!(f, "Cannot find datapack '{}'", name.cyan())
+			}
+			MergeError::DuplicateDatapack(name) => write!(
+				f,
+				"Datapack '{}' appear more than once in merge order",
+				name.cyan()
+			),
+			MergeError::InvalidRule(rule) => write!(
+				f,
+				"'{}' is not a valid rule, expected '<pattern>=<keep-core|union|error|drop>'",
+				rule.cyan()
+			),
+			MergeError::InvalidLevel(level, compression) => match compression.levels() {
+				Some(levels) => write!(
+					f,
+					"'{}' is not a valid level for {} compression, expected {} to {}",
+					level.cyan(),
+					compression.name(),
+					levels.start(),
+					levels.end()
+				),
+				None => write!(
+					f,
+					"{} zip is not compressed, '{}' cannot be used with it",
+					compression.name(),
+					"--level".cyan()
+				),
+			},
+			MergeError::Unresolved(count) => {
+				write!(f, "Merge failed with {} unresolved problem(s).", count)
+			}
 		}
 	}
 }
@@ -77,13 +308,184 @@ impl From<TreeError> for MergeError {
 	}
 }
 
-use zip::CompressionMethod;
-pub fn get_compression_method() -> CompressionMethod {
-	if cfg!(feature = "bzip2") {
-		CompressionMethod::Bzip2
-	} else if cfg!(feature = "deflate") {
-		CompressionMethod::Deflated
-	} else {
-		CompressionMethod::Stored
+impl From<ManifestError> for MergeError {
+	fn from(error: ManifestError) -> MergeError {
+		MergeError::Manifest(error)
+	}
+}
+
+use std::convert::TryFrom;
+use time::OffsetDateTime;
+use zip::DateTime;
+/// Modification time given to every entries of zipped output
+///
+/// Taken from `SOURCE_DATE_EPOCH` when it is set, otherwise it's the earliest time zip can store.
+/// Either way compiling the same datapacks twice gives byte-identical zip.
+pub fn get_zip_timestamp() -> DateTime {
+	std::env::var("SOURCE_DATE_EPOCH")
+		.ok()
+		.and_then(|value| value.trim().parse::<i64>().ok())
+		.map(timestamp_from_epoch)
+		.unwrap_or_default()
+}
+
+/// Convert unix time in seconds into UTC date, times that zip cannot store (before 1980 or after 2107) are replaced by 1980-01-01
+fn timestamp_from_epoch(seconds: i64) -> DateTime {
+	OffsetDateTime::from_unix_timestamp(seconds)
+		.ok()
+		.and_then(|time| DateTime::try_from(time).ok())
+		.unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn names(values: &[&str]) -> Vec<String> {
+		values.iter().map(|value| value.to_string()).collect()
+	}
+
+	#[test]
+	fn parse_order_skip_comments() {
+		let content = "# lowest priority first\nalpha\n\n  beta  \n#gamma\ndelta\n";
+		assert_eq!(parse_order(content), names(&["alpha", "beta", "delta"]));
+	}
+
+	#[test]
+	fn prioritize_core_move_to_end() {
+		let order = prioritize_core(names(&["alpha", "beta", "gamma"]), "alpha");
+		assert_eq!(order, names(&["beta", "gamma", "alpha"]));
+	}
+
+	#[test]
+	fn prioritize_core_append_missing() {
+		let order = prioritize_core(names(&["alpha"]), "beta");
+		assert_eq!(order, names(&["alpha", "beta"]));
+	}
+
+	#[test]
+	fn validate_order_reject_duplicate() {
+		let available = names(&["alpha", "beta"]);
+		let order = names(&["alpha", "beta", "alpha"]);
+		match validate_order(&order, &available) {
+			Err(MergeError::DuplicateDatapack(name)) => assert_eq!(name, "alpha"),
+			other => panic!("Unexpected result: {:?}", other),
+		}
+	}
+
+	#[test]
+	fn validate_order_reject_unknown() {
+		let available = names(&["alpha"]);
+		match validate_order(&names(&["beta"]), &available) {
+			Err(MergeError::UnknownDatapack(name)) => assert_eq!(name, "beta"),
+			other => panic!("Unexpected result: {:?}", other),
+		}
+	}
+
+	fn create_datapack(path: &Path) {
+		fs::create_dir_all(path.join("data")).unwrap();
+		fs::write(path.join("pack.mcmeta"), "{}").unwrap();
+	}
+
+	#[test]
+	fn discover_datapacks_recursively() {
+		use std::io::Write;
+		use zip::write::FileOptions;
+		use zip::ZipWriter;
+
+		let directory = tempfile::tempdir().unwrap();
+		let root = directory.path();
+		create_datapack(&root.join("emilia"));
+		create_datapack(&root.join("collection/nested/ram"));
+
+		let server = root.join("server");
+		let world = server.join("survival");
+		fs::create_dir_all(world.join("region")).unwrap();
+		fs::write(server.join("server.properties"), "level-name=survival\n").unwrap();
+		fs::write(world.join("level.dat"), "").unwrap();
+		fs::write(world.join("region/r.0.0.mca"), "").unwrap();
+		create_datapack(&world.join("datapacks/rem"));
+
+		let mut zip = ZipWriter::new(fs::File::create(world.join("datapacks/rem.zip")).unwrap());
+		for name in &["pack.mcmeta", "data/rem/tags/functions/load.json"] {
+			zip.start_file(*name, FileOptions::default()).unwrap();
+			zip.write_all(b"{}").unwrap();
+		}
+		zip.finish().unwrap();
+
+		let discovery = discover_datapacks(root, true, PackType::Data).unwrap();
+		let mut found: Vec<String> = discovery
+			.datapacks
+			.iter()
+			.map(|loader| loader.name.clone())
+			.collect();
+		found.sort();
+		assert_eq!(
+			found,
+			names(&[
+				"collection/nested/ram",
+				"emilia",
+				"server/survival/datapacks/rem"
+			])
+		);
+
+		match discovery.rejected.as_slice() {
+			[(path, Rejection::Duplicate(folder))] => {
+				assert_eq!(path, &world.join("datapacks/rem.zip"));
+				assert_eq!(folder, &world.join("datapacks/rem"));
+			}
+			other => panic!("Unexpected result: {:?}", other),
+		}
+
+		let discovery = discover_datapacks(root, false, PackType::Data).unwrap();
+		let found: Vec<&str> = discovery
+			.datapacks
+			.iter()
+			.map(|loader| loader.name.as_str())
+			.collect();
+		assert_eq!(found, vec!["emilia"]);
+	}
+
+	#[test]
+	fn reject_files_that_are_not_zip() {
+		let directory = tempfile::tempdir().unwrap();
+		let root = directory.path();
+		create_datapack(&root.join("emilia"));
+		fs::write(root.join("README.txt"), "Not a datapack").unwrap();
+
+		let discovery = discover_datapacks(root, false, PackType::Data).unwrap();
+		assert_eq!(discovery.datapacks.len(), 1);
+		match discovery.rejected.as_slice() {
+			[(path, Rejection::NotPack)] => assert_eq!(path, &root.join("README.txt")),
+			other => panic!("Unexpected result: {:?}", other),
+		}
+	}
+
+	fn date_of(time: DateTime) -> (u16, u8, u8, u8, u8, u8) {
+		(
+			time.year(),
+			time.month(),
+			time.day(),
+			time.hour(),
+			time.minute(),
+			time.second(),
+		)
+	}
+
+	#[test]
+	fn zip_timestamp_from_epoch() {
+		assert_eq!(
+			date_of(timestamp_from_epoch(1_582_934_400)),
+			(2020, 2, 29, 0, 0, 0)
+		);
+		assert_eq!(
+			date_of(timestamp_from_epoch(951_868_799)),
+			(2000, 2, 29, 23, 59, 59)
+		);
+		assert_eq!(date_of(timestamp_from_epoch(0)), (1980, 1, 1, 0, 0, 0));
+		assert_eq!(
+			date_of(timestamp_from_epoch(i64::MAX / 2)),
+			(1980, 1, 1, 0, 0, 0)
+		);
 	}
 }